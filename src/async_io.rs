@@ -0,0 +1,52 @@
+use crate::{I2c, I2cError, I2cResult, IoctlError};
+use std::sync::Arc;
+
+// the ioctls block until the transfer completes, so they are run on tokio's
+// blocking thread pool rather than on the runtime. the handle is shared with
+// the pool through the Arc, so no descriptor is opened per transfer
+impl I2c {
+    pub async fn i2c_read_async(
+        self: &Arc<Self>,
+        register: u8,
+        bytes: usize,
+    ) -> I2cResult<Vec<u8>> {
+        let handle = Arc::clone(self);
+        spawn(move || handle.i2c_read_bytes(register, bytes)).await
+    }
+
+    pub async fn i2c_write_async(self: &Arc<Self>, register: u8, buffer: &[u8]) -> I2cResult<()> {
+        let handle = Arc::clone(self);
+        let buffer = buffer.to_vec();
+        spawn(move || handle.i2c_write(register, &buffer)).await
+    }
+}
+
+async fn spawn<T, F>(f: F) -> I2cResult<T>
+where
+    F: FnOnce() -> I2cResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        // the closure itself panicked, so pass the panic on to the caller
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(I2cError::IoctlError(IoctlError::IoctlError(e.into()))),
+    }
+}
+
+#[test]
+fn test_read_write_async() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let handle = Arc::new(I2c::open(0x76).unwrap());
+
+    runtime.block_on(async {
+        let id = handle.i2c_read_async(0xD0, 1).await.unwrap();
+        assert_eq!(id, vec![0x61]);
+
+        handle.i2c_write_async(0x74, &[0b0000_0000]).await.unwrap();
+        let ctrl_meas = handle.i2c_read_async(0x74, 1).await.unwrap();
+        assert_eq!(ctrl_meas, vec![0b0000_0000]);
+    });
+}
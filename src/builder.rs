@@ -0,0 +1,145 @@
+use crate::{I2c, I2cResult};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// restricts the transfers a handle may make, as a safeguard beyond the file
+// permissions. ReadOnly still allows selecting a register to read from, i.e. a
+// write followed by a read in the same transfer, and both allow transfers that
+// carry no data, such as a quick write. disallowed transfers fail with
+// AccessDenied before reaching the bus
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum AccessMode {
+    ReadOnly,
+    WriteOnly,
+    #[default]
+    ReadWrite,
+}
+
+// configures the adapter before the address is probed, so the handle is never
+// used half-configured. defaults to bus 1 and leaves anything not set as the
+// kernel has it
+#[derive(Debug, Clone)]
+pub struct I2cBuilder {
+    path: PathBuf,
+    addr: u16,
+    retries: Option<u32>,
+    timeout: Option<Duration>,
+    pec: Option<bool>,
+    access: AccessMode,
+    close_on_exec: bool,
+    nonblocking: bool,
+}
+
+impl I2cBuilder {
+    pub fn new(addr: u16) -> Self {
+        Self {
+            path: PathBuf::from("/dev/i2c-1"),
+            addr,
+            retries: None,
+            timeout: None,
+            pec: None,
+            access: AccessMode::ReadWrite,
+            close_on_exec: true,
+            nonblocking: false,
+        }
+    }
+
+    pub fn bus(self, bus: u8) -> Self {
+        self.path(format!("/dev/i2c-{}", bus))
+    }
+
+    pub fn path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    pub fn address(mut self, addr: u16) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn pec(mut self, enabled: bool) -> Self {
+        self.pec = Some(enabled);
+        self
+    }
+
+    pub fn access(mut self, access: AccessMode) -> Self {
+        self.access = access;
+        self
+    }
+
+    // the descriptor is closed in child processes unless disabled here, for
+    // children that are meant to inherit it
+    pub fn close_on_exec(mut self, enabled: bool) -> Self {
+        self.close_on_exec = enabled;
+        self
+    }
+
+    // sets O_NONBLOCK on the descriptor, e.g. for registering it with an
+    // event loop through AsRawFd. i2c-dev itself ignores the flag: ioctls and
+    // plain reads and writes still block until the adapter finishes the
+    // transfer or times out
+    pub fn nonblocking(mut self, enabled: bool) -> Self {
+        self.nonblocking = enabled;
+        self
+    }
+
+    pub fn build(&self) -> I2cResult<I2c> {
+        let handle = I2c::open_unprobed_with(&self.path, self.addr, self.access)?;
+        if !self.close_on_exec {
+            handle.set_close_on_exec(false)?;
+        }
+        if self.nonblocking {
+            handle.set_nonblocking(true)?;
+        }
+        if let Some(retries) = self.retries {
+            handle.set_retries(retries)?;
+        }
+        if let Some(timeout) = self.timeout {
+            handle.set_timeout(timeout)?;
+        }
+        if let Some(enabled) = self.pec {
+            handle.set_pec(enabled)?;
+        }
+
+        handle.check_responds()?;
+        Ok(handle)
+    }
+}
+
+#[test]
+fn test_missing_bus() {
+    let result = I2cBuilder::new(0x76).bus(255).retries(3).build();
+    assert!(matches!(result, Err(crate::I2cError::FileError(_))));
+}
+
+#[test]
+fn test_build() {
+    use std::os::unix::io::AsRawFd;
+
+    let handle = I2cBuilder::new(0x76)
+        .bus(1)
+        .retries(3)
+        .timeout(Duration::from_millis(100))
+        .pec(false)
+        .close_on_exec(false)
+        .nonblocking(true)
+        .build()
+        .unwrap();
+    assert_eq!(handle.retries(), Some(3));
+    let flags = unsafe { libc::fcntl(handle.as_raw_fd(), libc::F_GETFD) };
+    assert_eq!(flags & libc::FD_CLOEXEC, 0);
+    let flags = unsafe { libc::fcntl(handle.as_raw_fd(), libc::F_GETFL) };
+    assert_ne!(flags & libc::O_NONBLOCK, 0);
+    assert_eq!(handle.i2c_read_bytes(0xD0, 1).unwrap(), [0x61]);
+}
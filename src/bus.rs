@@ -0,0 +1,85 @@
+use crate::{check_addr_range, I2c, I2cResult};
+use std::path::Path;
+use std::sync::Mutex;
+
+// the bus is a shared, serial resource, so a single file descriptor is shared
+// between devices and every transaction holds the lock for its duration
+#[derive(Debug)]
+pub struct I2cBus {
+    handle: Mutex<I2c>,
+}
+
+impl I2cBus {
+    pub fn open(bus: u8) -> I2cResult<Self> {
+        Self::open_path(format!("/dev/i2c-{}", bus))
+    }
+
+    pub fn open_path<P: AsRef<Path>>(path: P) -> I2cResult<Self> {
+        let handle = I2c::open_unprobed(path, 0)?;
+        Ok(Self {
+            handle: Mutex::new(handle),
+        })
+    }
+
+    pub fn device(&self, addr: u16) -> I2cResult<I2cDevice<'_>> {
+        let func = *self.handle.lock().unwrap().functionality();
+        check_addr_range(func, addr)?;
+        Ok(I2cDevice { bus: self, addr })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct I2cDevice<'a> {
+    bus: &'a I2cBus,
+    addr: u16,
+}
+
+impl I2cDevice<'_> {
+    pub fn addr(&self) -> u16 {
+        self.addr
+    }
+
+    // runs f with the bus locked and addressed to this device, so nothing
+    // else on the bus can interleave with the transactions in f
+    pub fn with<T, F>(&self, f: F) -> I2cResult<T>
+    where
+        F: FnOnce(&I2c) -> I2cResult<T>,
+    {
+        let mut handle = self.bus.handle.lock().unwrap();
+        if handle.addr != self.addr {
+            handle.set_addr_unchecked(self.addr);
+        }
+        f(&handle)
+    }
+
+    pub fn i2c_read_bytes(&self, register: u8, bytes: usize) -> I2cResult<Vec<u8>> {
+        self.with(|handle| handle.i2c_read_bytes(register, bytes))
+    }
+
+    pub fn i2c_read(&self, register: u8, buffer: &mut [u8]) -> I2cResult<usize> {
+        self.with(|handle| handle.i2c_read(register, buffer))
+    }
+
+    pub fn i2c_write(&self, register: u8, buffer: &[u8]) -> I2cResult<()> {
+        self.with(|handle| handle.i2c_write(register, buffer))
+    }
+}
+
+#[test]
+fn test_missing_bus() {
+    let result = I2cBus::open(255);
+    assert!(matches!(result, Err(crate::I2cError::FileError(_))));
+}
+
+#[test]
+fn test_shared_bus() {
+    let bus = I2cBus::open(1).unwrap();
+    let bme680 = bus.device(0x76).unwrap();
+    let missing = bus.device(0x08).unwrap();
+
+    std::thread::scope(|s| {
+        s.spawn(|| assert_eq!(bme680.i2c_read_bytes(0xD0, 1).unwrap(), vec![0x61]));
+        s.spawn(|| assert!(missing.i2c_read_bytes(0xD0, 1).is_err()));
+    });
+    assert_eq!(bme680.i2c_read_bytes(0xD0, 1).unwrap(), vec![0x61]);
+}
@@ -0,0 +1,408 @@
+use crate::Functionality;
+use libc::c_int;
+use std::path::PathBuf;
+
+// the messages are derived with thiserror by default. without the thiserror
+// feature, Display and Error are implemented by hand with the same output
+#[derive(Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum IoctlError {
+    #[cfg_attr(
+        feature = "thiserror",
+        error("missing functionality required for ioctl call")
+    )]
+    FunctionalityError(Functionality),
+    #[cfg_attr(
+        feature = "thiserror",
+        error("only {completed} of {total} messages transferred")
+    )]
+    PartialTransfer { completed: usize, total: usize },
+    #[cfg_attr(
+        feature = "thiserror",
+        error("transfer not permitted by the access mode of the handle")
+    )]
+    AccessDenied,
+    #[cfg_attr(feature = "thiserror", error("{}", Errno(.0)))]
+    IoctlError(std::io::Error),
+}
+
+impl std::convert::From<Functionality> for IoctlError {
+    fn from(arg: Functionality) -> Self {
+        Self::FunctionalityError(arg)
+    }
+}
+
+impl std::convert::From<std::io::Error> for IoctlError {
+    fn from(arg: std::io::Error) -> Self {
+        Self::IoctlError(arg)
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum I2cError {
+    #[cfg_attr(feature = "thiserror", error("failed to open i2c device"))]
+    FileError(#[cfg_attr(feature = "thiserror", source)] std::io::Error),
+    #[cfg_attr(
+        feature = "thiserror",
+        error("permission denied opening i2c device, check the user is in the i2c group or a udev rule grants access")
+    )]
+    PermissionDenied(#[cfg_attr(feature = "thiserror", source)] std::io::Error),
+    #[cfg_attr(
+        feature = "thiserror",
+        error("{} is not an i2c device", .path.display())
+    )]
+    NotAnI2cDevice { path: PathBuf },
+    #[cfg_attr(feature = "thiserror", error("failed on i2c read request"))]
+    ReadError {
+        addr: u16,
+        // the register or SMBUS command read from, where there is one
+        register: Option<u8>,
+        #[cfg_attr(feature = "thiserror", source)]
+        source: IoctlError,
+    },
+    #[cfg_attr(feature = "thiserror", error("failed on i2c write request"))]
+    WriteError {
+        addr: u16,
+        register: Option<u8>,
+        #[cfg_attr(feature = "thiserror", source)]
+        source: IoctlError,
+    },
+    #[cfg_attr(feature = "thiserror", error("failed on i2c buffer execute"))]
+    BufferError(#[cfg_attr(feature = "thiserror", source)] IoctlError),
+    #[cfg_attr(feature = "thiserror", error("i2c address unresponsive"))]
+    AddressError(#[cfg_attr(feature = "thiserror", source)] IoctlError),
+    #[cfg_attr(
+        feature = "thiserror",
+        error("no acknowledgement from i2c device: {}", Errno(.source))
+    )]
+    NoAcknowledge {
+        // the device addressed, unless the failure came from a buffer whose
+        // messages may address several
+        addr: Option<u16>,
+        register: Option<u8>,
+        #[cfg_attr(feature = "thiserror", source)]
+        source: std::io::Error,
+    },
+    #[cfg_attr(
+        feature = "thiserror",
+        error("i2c bus fault: {}", Errno(.source))
+    )]
+    BusError {
+        addr: Option<u16>,
+        register: Option<u8>,
+        #[cfg_attr(feature = "thiserror", source)]
+        source: std::io::Error,
+    },
+    #[cfg_attr(
+        feature = "thiserror",
+        error("address too long for supported address range")
+    )]
+    AddressRangeError,
+    #[cfg_attr(
+        feature = "thiserror",
+        error("buffer of {len} bytes exceeds the maximum message length")
+    )]
+    LengthError { len: usize },
+    #[cfg_attr(feature = "thiserror", error("read into an empty buffer"))]
+    EmptyBuffer,
+    #[cfg_attr(
+        feature = "thiserror",
+        error("{count} messages exceeds the maximum of 42 in a single transfer")
+    )]
+    TooManyMessages { count: usize },
+    #[cfg_attr(
+        feature = "thiserror",
+        error("contradictory message flags {flags:#06x}")
+    )]
+    FlagError { flags: u16 },
+    #[cfg_attr(feature = "thiserror", error("mux channel {channel} out of range"))]
+    InvalidChannel { channel: u8 },
+    #[cfg_attr(
+        feature = "thiserror",
+        error("read buffer overlaps another buffer of the same transfer")
+    )]
+    BufferAlias,
+    #[cfg_attr(feature = "thiserror", error("timeout outside the supported range"))]
+    TimeoutRangeError,
+    #[cfg_attr(
+        feature = "thiserror",
+        error("block of {len} bytes exceeds the smbus maximum of 32")
+    )]
+    BlockLengthError { len: usize },
+    #[cfg_attr(
+        feature = "thiserror",
+        error("operation not permitted by the access mode of the handle")
+    )]
+    AccessDenied,
+    #[cfg_attr(feature = "thiserror", error("timed out waiting for the device"))]
+    Timeout,
+    #[cfg_attr(
+        feature = "thiserror",
+        error("no i2c bus supports the required functionality")
+    )]
+    NoMatchingBus { required: Functionality },
+    #[cfg_attr(feature = "thiserror", error(transparent))]
+    IoctlError(IoctlError),
+}
+
+impl std::convert::From<IoctlError> for I2cError {
+    fn from(arg: IoctlError) -> Self {
+        Self::IoctlError(arg)
+    }
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl std::fmt::Display for IoctlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoctlError::FunctionalityError(_) => {
+                write!(f, "missing functionality required for ioctl call")
+            }
+            IoctlError::PartialTransfer { completed, total } => {
+                write!(f, "only {} of {} messages transferred", completed, total)
+            }
+            IoctlError::AccessDenied => {
+                write!(f, "transfer not permitted by the access mode of the handle")
+            }
+            IoctlError::IoctlError(err) => write!(f, "{}", Errno(err)),
+        }
+    }
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl std::error::Error for IoctlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IoctlError::IoctlError(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl std::fmt::Display for I2cError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            I2cError::FileError(_) => write!(f, "failed to open i2c device"),
+            I2cError::PermissionDenied(_) => write!(
+                f,
+                "permission denied opening i2c device, check the user is in the i2c group or a udev rule grants access"
+            ),
+            I2cError::NotAnI2cDevice { path } => {
+                write!(f, "{} is not an i2c device", path.display())
+            }
+            I2cError::ReadError { .. } => write!(f, "failed on i2c read request"),
+            I2cError::WriteError { .. } => write!(f, "failed on i2c write request"),
+            I2cError::BufferError(_) => write!(f, "failed on i2c buffer execute"),
+            I2cError::AddressError(_) => write!(f, "i2c address unresponsive"),
+            I2cError::NoAcknowledge { source, .. } => {
+                write!(f, "no acknowledgement from i2c device: {}", Errno(source))
+            }
+            I2cError::BusError { source, .. } => {
+                write!(f, "i2c bus fault: {}", Errno(source))
+            }
+            I2cError::AddressRangeError => {
+                write!(f, "address too long for supported address range")
+            }
+            I2cError::LengthError { len } => write!(
+                f,
+                "buffer of {} bytes exceeds the maximum message length",
+                len
+            ),
+            I2cError::EmptyBuffer => write!(f, "read into an empty buffer"),
+            I2cError::TooManyMessages { count } => write!(
+                f,
+                "{} messages exceeds the maximum of 42 in a single transfer",
+                count
+            ),
+            I2cError::FlagError { flags } => {
+                write!(f, "contradictory message flags {:#06x}", flags)
+            }
+            I2cError::InvalidChannel { channel } => {
+                write!(f, "mux channel {} out of range", channel)
+            }
+            I2cError::BufferAlias => {
+                write!(f, "read buffer overlaps another buffer of the same transfer")
+            }
+            I2cError::TimeoutRangeError => write!(f, "timeout outside the supported range"),
+            I2cError::BlockLengthError { len } => {
+                write!(f, "block of {} bytes exceeds the smbus maximum of 32", len)
+            }
+            I2cError::AccessDenied => {
+                write!(f, "operation not permitted by the access mode of the handle")
+            }
+            I2cError::Timeout => write!(f, "timed out waiting for the device"),
+            I2cError::NoMatchingBus { .. } => {
+                write!(f, "no i2c bus supports the required functionality")
+            }
+            I2cError::IoctlError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl std::error::Error for I2cError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            I2cError::FileError(err)
+            | I2cError::PermissionDenied(err)
+            | I2cError::NoAcknowledge { source: err, .. }
+            | I2cError::BusError { source: err, .. } => Some(err),
+            I2cError::ReadError { source: err, .. }
+            | I2cError::WriteError { source: err, .. }
+            | I2cError::BufferError(err)
+            | I2cError::AddressError(err) => Some(err),
+            I2cError::IoctlError(err) => std::error::Error::source(err),
+            _ => None,
+        }
+    }
+}
+
+// names the error codes adapters commonly report, as the io::Error message
+// only gives the number, which also differs between architectures
+struct Errno<'a>(&'a std::io::Error);
+
+impl std::fmt::Display for Errno<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.raw_os_error() {
+            Some(libc::ENXIO) => write!(f, "no device at address"),
+            Some(libc::EREMOTEIO) => write!(f, "device did not acknowledge"),
+            Some(libc::ETIMEDOUT) => write!(f, "bus timeout"),
+            Some(libc::EBUSY) => write!(f, "bus busy"),
+            _ => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl I2cError {
+    // the error code the kernel reported for a transfer, if any
+    pub(crate) fn raw_os_error(&self) -> Option<c_int> {
+        match self {
+            I2cError::NoAcknowledge { source: err, .. }
+            | I2cError::BusError { source: err, .. } => err.raw_os_error(),
+            I2cError::ReadError {
+                source: IoctlError::IoctlError(err),
+                ..
+            }
+            | I2cError::WriteError {
+                source: IoctlError::IoctlError(err),
+                ..
+            }
+            | I2cError::BufferError(IoctlError::IoctlError(err))
+            | I2cError::AddressError(IoctlError::IoctlError(err))
+            | I2cError::IoctlError(IoctlError::IoctlError(err)) => err.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    // the device nodes are usually only accessible to root and the i2c group,
+    // so a failure to open one is most often down to permissions
+    pub(crate) fn open(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::PermissionDenied => I2cError::PermissionDenied(err),
+            _ => I2cError::FileError(err),
+        }
+    }
+
+    pub(crate) fn read(addr: u16, register: Option<u8>, err: IoctlError) -> Self {
+        classify(err, Some(addr), register).unwrap_or_else(|source| I2cError::ReadError {
+            addr,
+            register,
+            source,
+        })
+    }
+
+    pub(crate) fn write(addr: u16, register: Option<u8>, err: IoctlError) -> Self {
+        classify(err, Some(addr), register).unwrap_or_else(|source| I2cError::WriteError {
+            addr,
+            register,
+            source,
+        })
+    }
+
+    pub(crate) fn buffer(addr: Option<u16>, err: IoctlError) -> Self {
+        classify(err, addr, None).unwrap_or_else(I2cError::BufferError)
+    }
+}
+
+// a missing acknowledgement or a bus fault is reported as such rather than as a
+// failure of the particular request. error codes as described in the kernel's
+// i2c fault-codes documentation
+fn classify(
+    err: IoctlError,
+    addr: Option<u16>,
+    register: Option<u8>,
+) -> Result<I2cError, IoctlError> {
+    let err = match err {
+        IoctlError::IoctlError(err) => err,
+        IoctlError::AccessDenied => return Ok(I2cError::AccessDenied),
+        err => return Err(err),
+    };
+
+    match err.raw_os_error() {
+        Some(libc::ENXIO) | Some(libc::EREMOTEIO) => Ok(I2cError::NoAcknowledge {
+            addr,
+            register,
+            source: err,
+        }),
+        Some(libc::EAGAIN) | Some(libc::EIO) | Some(libc::EPROTO) | Some(libc::ETIMEDOUT) => {
+            Ok(I2cError::BusError {
+                addr,
+                register,
+                source: err,
+            })
+        }
+        _ => Err(IoctlError::IoctlError(err)),
+    }
+}
+
+#[test]
+fn test_display() {
+    use std::error::Error;
+
+    let err = I2cError::ReadError {
+        addr: 0x76,
+        register: Some(0xD0),
+        source: IoctlError::FunctionalityError(Functionality::I2C),
+    };
+    assert_eq!(format!("{}", err), "failed on i2c read request");
+    assert_eq!(
+        format!("{}", err.source().unwrap()),
+        "missing functionality required for ioctl call"
+    );
+
+    let err = std::io::Error::from_raw_os_error(libc::EINVAL);
+    let err = I2cError::IoctlError(IoctlError::IoctlError(err));
+    assert_eq!(format!("{}", err), "Invalid argument (os error 22)");
+    assert!(err.source().is_none());
+
+    let err = std::io::Error::from_raw_os_error(libc::EBUSY);
+    let err = I2cError::IoctlError(IoctlError::IoctlError(err));
+    assert_eq!(format!("{}", err), "bus busy");
+
+    let err = std::io::Error::from_raw_os_error(libc::ENXIO);
+    let err = I2cError::read(0x76, None, IoctlError::IoctlError(err));
+    assert_eq!(
+        format!("{}", err),
+        "no acknowledgement from i2c device: no device at address"
+    );
+
+    let err = std::io::Error::from_raw_os_error(libc::ETIMEDOUT);
+    let err = I2cError::write(0x76, None, IoctlError::IoctlError(err));
+    assert_eq!(format!("{}", err), "i2c bus fault: bus timeout");
+
+    let err = I2cError::open(std::io::Error::from_raw_os_error(libc::EACCES));
+    assert!(matches!(err, I2cError::PermissionDenied(_)));
+    assert!(format!("{}", err).contains("i2c group"));
+    assert_eq!(
+        format!("{}", err.source().unwrap()),
+        "Permission denied (os error 13)"
+    );
+
+    let err = I2cError::LengthError { len: 65536 };
+    assert_eq!(
+        format!("{}", err),
+        "buffer of 65536 bytes exceeds the maximum message length"
+    );
+}
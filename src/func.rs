@@ -1,3 +1,4 @@
+use bitflags::bitflags;
 use libc::c_ulong;
 
 // functionality parameters
@@ -22,135 +23,337 @@ pub const I2C_FUNC_SMBUS_WRITE_BLOCK: c_ulong = 0x08000000;
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct Functionality(pub c_ulong);
 
+bitflags! {
+    impl Functionality: c_ulong {
+        const I2C = I2C_FUNC_I2C;
+        const TEN_BIT_ADDR = I2C_FUNC_10BIT_ADDR;
+        const PROTOCOL_MANGLING = I2C_FUNC_PROTOCOL_MANGLING;
+        const SMBUS_PEC = I2C_FUNC_SMBUS_PEC;
+        const SMBUS_BLOCK_PROC_CALL = I2C_FUNC_SMBUS_BLOCK_PROC_CALL;
+        const SMBUS_QUICK = I2C_FUNC_SMBUS_QUICK;
+        const SMBUS_READ_BYTE = I2C_FUNC_SMBUS_READ_BYTE;
+        const SMBUS_WRITE_BYTE = I2C_FUNC_SMBUS_WRITE_BYTE;
+        const SMBUS_READ_BYTE_DATA = I2C_FUNC_SMBUS_READ_BYTE_DATA;
+        const SMBUS_WRITE_BYTE_DATA = I2C_FUNC_SMBUS_WRITE_BYTE_DATA;
+        const SMBUS_READ_WORD_DATA = I2C_FUNC_SMBUS_READ_WORD_DATA;
+        const SMBUS_WRITE_WORD_DATA = I2C_FUNC_SMBUS_WRITE_WORD_DATA;
+        const SMBUS_PROC_CALL = I2C_FUNC_SMBUS_PROC_CALL;
+        const SMBUS_READ_BLOCK_DATA = I2C_FUNC_SMBUS_READ_BLOCK_DATA;
+        const SMBUS_WRITE_BLOCK_DATA = I2C_FUNC_SMBUS_WRITE_BLOCK_DATA;
+        const SMBUS_READ_BLOCK = I2C_FUNC_SMBUS_READ_BLOCK;
+        const SMBUS_WRITE_BLOCK = I2C_FUNC_SMBUS_WRITE_BLOCK;
+    }
+}
+
+// human readable name of each flag, in the order they are displayed
+const NAMES: [(Functionality, &str); 17] = [
+    (Functionality::I2C, "I2C"),
+    (Functionality::TEN_BIT_ADDR, "10 BIT ADDR"),
+    (Functionality::PROTOCOL_MANGLING, "PROTOCOL MANGLING"),
+    (Functionality::SMBUS_PEC, "SMBUS PEC"),
+    (
+        Functionality::SMBUS_BLOCK_PROC_CALL,
+        "SMBUS BLOCK PROC CALL",
+    ),
+    (Functionality::SMBUS_QUICK, "SMBUS QUICK"),
+    (Functionality::SMBUS_READ_BYTE, "SMBUS READ BYTE"),
+    (Functionality::SMBUS_WRITE_BYTE, "SMBUS WRITE BYTE"),
+    (Functionality::SMBUS_READ_BYTE_DATA, "SMBUS READ BYTE DATA"),
+    (
+        Functionality::SMBUS_WRITE_BYTE_DATA,
+        "SMBUS WRITE BYTE DATA",
+    ),
+    (Functionality::SMBUS_READ_WORD_DATA, "SMBUS READ WORD DATA"),
+    (
+        Functionality::SMBUS_WRITE_WORD_DATA,
+        "SMBUS WRITE WORD DATA",
+    ),
+    (Functionality::SMBUS_PROC_CALL, "SMBUS PROC CALL"),
+    (
+        Functionality::SMBUS_READ_BLOCK_DATA,
+        "SMBUS READ BLOCK DATA",
+    ),
+    (
+        Functionality::SMBUS_WRITE_BLOCK_DATA,
+        "SMBUS WRITE BLOCK DATA",
+    ),
+    (Functionality::SMBUS_READ_BLOCK, "SMBUS READ BLOCK"),
+    (Functionality::SMBUS_WRITE_BLOCK, "SMBUS WRITE BLOCK"),
+];
+
+// the transfers this crate implements, each needing its own functionality
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Operation {
+    // i2c_read, i2c_write, write_read and I2cBuffer, over I2C_RDWR
+    I2cTransfer,
+    SmbusQuick,
+    SmbusReadByte,
+    SmbusWriteByte,
+    SmbusReadByteData,
+    SmbusWriteByteData,
+    SmbusReadWordData,
+    SmbusWriteWordData,
+    SmbusProcessCall,
+    SmbusReadBlockData,
+    SmbusWriteBlockData,
+    SmbusBlockProcessCall,
+    ReadI2cBlockData,
+    WriteI2cBlockData,
+}
+
+// the functionality each operation needs, in the order they are listed
+const OPERATIONS: [(Operation, Functionality); 14] = [
+    (Operation::I2cTransfer, Functionality::I2C),
+    (Operation::SmbusQuick, Functionality::SMBUS_QUICK),
+    (Operation::SmbusReadByte, Functionality::SMBUS_READ_BYTE),
+    (Operation::SmbusWriteByte, Functionality::SMBUS_WRITE_BYTE),
+    (
+        Operation::SmbusReadByteData,
+        Functionality::SMBUS_READ_BYTE_DATA,
+    ),
+    (
+        Operation::SmbusWriteByteData,
+        Functionality::SMBUS_WRITE_BYTE_DATA,
+    ),
+    (
+        Operation::SmbusReadWordData,
+        Functionality::SMBUS_READ_WORD_DATA,
+    ),
+    (
+        Operation::SmbusWriteWordData,
+        Functionality::SMBUS_WRITE_WORD_DATA,
+    ),
+    (Operation::SmbusProcessCall, Functionality::SMBUS_PROC_CALL),
+    (
+        Operation::SmbusReadBlockData,
+        Functionality::SMBUS_READ_BLOCK_DATA,
+    ),
+    (
+        Operation::SmbusWriteBlockData,
+        Functionality::SMBUS_WRITE_BLOCK_DATA,
+    ),
+    (
+        Operation::SmbusBlockProcessCall,
+        Functionality::SMBUS_BLOCK_PROC_CALL,
+    ),
+    (Operation::ReadI2cBlockData, Functionality::SMBUS_READ_BLOCK),
+    (
+        Operation::WriteI2cBlockData,
+        Functionality::SMBUS_WRITE_BLOCK,
+    ),
+];
+
 impl Functionality {
     pub fn new(func: c_ulong) -> Self {
         Functionality(func)
     }
 
+    // yields the human readable name of each supported capability, for
+    // individual flags use iter instead
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        NAMES
+            .iter()
+            .filter(move |(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+    }
+
+    // whether addr can be used on an adapter with this functionality: 10 bit
+    // addresses need TEN_BIT_ADDR, otherwise addresses are limited to 7 bits
+    pub fn validates_address(&self, addr: u16) -> bool {
+        let max = match self._10_bit_addr() {
+            true => 0b0011_1111_1111,
+            false => 0b0111_1111,
+        };
+        addr <= max
+    }
+
+    // the operations that can be used with an adapter of this functionality
+    pub fn supported_operations(&self) -> Vec<Operation> {
+        OPERATIONS
+            .iter()
+            .filter(|(_, func)| self.contains(*func))
+            .map(|(operation, _)| *operation)
+            .collect()
+    }
+
     pub fn i2c(&self) -> bool {
-        (self.0 & I2C_FUNC_I2C) > 0
+        self.contains(Self::I2C)
     }
 
     pub fn _10_bit_addr(&self) -> bool {
-        (self.0 & I2C_FUNC_10BIT_ADDR) > 0
+        self.contains(Self::TEN_BIT_ADDR)
     }
 
     pub fn protocol_mangling(&self) -> bool {
-        (self.0 & I2C_FUNC_PROTOCOL_MANGLING) > 0
+        self.contains(Self::PROTOCOL_MANGLING)
     }
 
     pub fn smbus_pec(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_PEC) > 0
+        self.contains(Self::SMBUS_PEC)
     }
 
     pub fn smbus_block_proc_call(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_BLOCK_PROC_CALL) > 0
+        self.contains(Self::SMBUS_BLOCK_PROC_CALL)
     }
 
     pub fn smbus_quick(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_QUICK) > 0
+        self.contains(Self::SMBUS_QUICK)
     }
 
     pub fn smbus_read_byte(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_READ_BYTE) > 0
+        self.contains(Self::SMBUS_READ_BYTE)
     }
 
     pub fn smbus_write_byte(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_WRITE_BYTE) > 0
+        self.contains(Self::SMBUS_WRITE_BYTE)
     }
 
     pub fn smbus_read_byte_data(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_READ_BYTE_DATA) > 0
+        self.contains(Self::SMBUS_READ_BYTE_DATA)
     }
 
     pub fn smbus_write_byte_data(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_WRITE_BYTE_DATA) > 0
+        self.contains(Self::SMBUS_WRITE_BYTE_DATA)
     }
 
     pub fn smbus_read_word_data(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_READ_WORD_DATA) > 0
+        self.contains(Self::SMBUS_READ_WORD_DATA)
     }
 
     pub fn smbus_write_word_data(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_WRITE_WORD_DATA) > 0
+        self.contains(Self::SMBUS_WRITE_WORD_DATA)
     }
 
     pub fn smbus_proc_call(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_PROC_CALL) > 0
+        self.contains(Self::SMBUS_PROC_CALL)
     }
 
     pub fn smbus_read_block_data(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_READ_BLOCK_DATA) > 0
+        self.contains(Self::SMBUS_READ_BLOCK_DATA)
     }
 
     pub fn smbus_write_block_data(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_WRITE_BLOCK_DATA) > 0
+        self.contains(Self::SMBUS_WRITE_BLOCK_DATA)
     }
 
     pub fn smbus_read_block(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_READ_BLOCK) > 0
+        self.contains(Self::SMBUS_READ_BLOCK)
     }
 
     pub fn smbus_write_block(&self) -> bool {
-        (self.0 & I2C_FUNC_SMBUS_WRITE_BLOCK) > 0
+        self.contains(Self::SMBUS_WRITE_BLOCK)
     }
 }
 
 impl std::fmt::Display for Functionality {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "|")?;
-        if self.i2c() {
-            write!(f, " I2C |")?;
-        };
-        if self._10_bit_addr() {
-            write!(f, " 10 BIT ADDR |")?;
-        };
-        if self.protocol_mangling() {
-            write!(f, " PROTOCOL MANGLING |")?;
-        };
-        if self.smbus_pec() {
-            write!(f, " SMBUS PEC |")?;
-        };
-        if self.smbus_block_proc_call() {
-            write!(f, " SMBUS BLOCK PROC CALL |")?;
-        };
-        if self.smbus_quick() {
-            write!(f, " SMBUS QUICK |")?;
-        };
-        if self.smbus_read_byte() {
-            write!(f, " SMBUS READ BYTE |")?;
-        };
-        if self.smbus_write_byte() {
-            write!(f, " SMBUS WRITE BYTE |")?;
-        };
-        if self.smbus_read_byte_data() {
-            write!(f, " SMBUS READ BYTE DATA |")?;
-        };
-        if self.smbus_write_byte_data() {
-            write!(f, " SMBUS WRITE BYTE DATA |")?;
-        };
-        if self.smbus_read_word_data() {
-            write!(f, " SMBUS READ WORD DATA |")?;
-        };
-        if self.smbus_write_word_data() {
-            write!(f, " SMBUS WRITE WORD DATA |")?;
-        };
-        if self.smbus_proc_call() {
-            write!(f, " SMBUS PROC CALL |")?;
+        for name in self.names() {
+            write!(f, " {} |", name)?;
         }
-        if self.smbus_read_block_data() {
-            write!(f, " SMBUS READ BLOCK DATA |")?;
-        };
-        if self.smbus_write_block_data() {
-            write!(f, " SMBUS WRITE BLOCK DATA |")?;
-        };
-        if self.smbus_read_block() {
-            write!(f, " SMBUS READ BLOCK |")?;
-        };
-        if self.smbus_write_block() {
-            write!(f, " SMBUS WRITE BLOCK |")?;
-        };
 
         Ok(())
     }
 }
+
+// serialized as a list of capability names, which unlike the raw mask does not
+// depend on the width of c_ulong. bits without a name are kept as hex strings
+#[cfg(feature = "serde")]
+impl serde::Serialize for Functionality {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+
+        let unnamed = self.difference(Self::all());
+        let mut seq = serializer.serialize_seq(None)?;
+        for (name, _) in self.iter_names() {
+            seq.serialize_element(name)?;
+        }
+        if !unnamed.is_empty() {
+            seq.serialize_element(&format!("{:#x}", unnamed.bits()))?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Functionality {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names.iter().try_fold(Self::empty(), |func, name| {
+            let flag = match name.strip_prefix("0x") {
+                Some(hex) => c_ulong::from_str_radix(hex, 16)
+                    .ok()
+                    .map(Self::from_bits_retain),
+                None => Self::from_name(name),
+            };
+            flag.map(|flag| func | flag)
+                .ok_or_else(|| D::Error::custom(format!("unknown functionality {}", name)))
+        })
+    }
+}
+
+#[test]
+fn test_flags() {
+    let func = Functionality::I2C | Functionality::SMBUS_QUICK;
+    assert_eq!(
+        func,
+        Functionality::new(I2C_FUNC_I2C | I2C_FUNC_SMBUS_QUICK)
+    );
+    assert!(func.i2c() && func.smbus_quick() && !func.smbus_pec());
+    assert_eq!(
+        func.iter().collect::<Vec<_>>(),
+        vec![Functionality::I2C, Functionality::SMBUS_QUICK]
+    );
+}
+
+#[test]
+fn test_set_operations() {
+    let required = Functionality::SMBUS_READ_BYTE_DATA | Functionality::SMBUS_WRITE_BYTE_DATA;
+    let func = Functionality::I2C | Functionality::SMBUS_READ_BYTE_DATA;
+    assert!(!func.contains(required));
+    assert_eq!(func & required, Functionality::SMBUS_READ_BYTE_DATA);
+    assert!(Functionality::empty().is_empty());
+    assert!(func.contains(Functionality::empty()));
+}
+
+#[test]
+fn test_validates_address() {
+    assert!(Functionality::I2C.validates_address(0x7F));
+    assert!(!Functionality::I2C.validates_address(0x80));
+    let func = Functionality::I2C | Functionality::TEN_BIT_ADDR;
+    assert!(func.validates_address(0x3FF));
+    assert!(!func.validates_address(0x400));
+}
+
+#[test]
+fn test_supported_operations() {
+    let func = Functionality::I2C | Functionality::SMBUS_QUICK | Functionality::SMBUS_PEC;
+    assert_eq!(
+        func.supported_operations(),
+        [Operation::I2cTransfer, Operation::SmbusQuick]
+    );
+    assert!(Functionality::empty().supported_operations().is_empty());
+}
+
+#[test]
+fn test_names() {
+    let func = Functionality::I2C | Functionality::TEN_BIT_ADDR | Functionality::SMBUS_QUICK;
+    assert_eq!(
+        func.names().collect::<Vec<_>>(),
+        vec!["I2C", "10 BIT ADDR", "SMBUS QUICK"]
+    );
+    assert_eq!(format!("{}", func), "| I2C | 10 BIT ADDR | SMBUS QUICK |");
+    assert_eq!(format!("{}", Functionality::empty()), "|");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let func = Functionality::I2C | Functionality::SMBUS_QUICK | Functionality::new(0x10);
+    let json = serde_json::to_string(&func).unwrap();
+    assert_eq!(json, r#"["I2C","SMBUS_QUICK","0x10"]"#);
+    assert_eq!(serde_json::from_str::<Functionality>(&json).unwrap(), func);
+    assert!(serde_json::from_str::<Functionality>(r#"["I2C","NOT_A_FLAG"]"#).is_err());
+}
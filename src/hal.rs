@@ -0,0 +1,127 @@
+use crate::messages::{I2cMessageBuffer, I2cReadWriteData, I2C_M_TEN};
+use crate::{i2c_rdwr_ioctl, Functionality, I2c, I2cError, I2cTransport, IoctlError};
+use embedded_hal::i2c::{
+    self, ErrorKind, NoAcknowledgeSource, Operation, SevenBitAddress, TenBitAddress,
+};
+
+impl i2c::Error for I2cError {
+    fn kind(&self) -> ErrorKind {
+        // error codes as described in the kernel's i2c fault-codes documentation
+        match self.raw_os_error() {
+            Some(libc::ENXIO) => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            Some(libc::EREMOTEIO) => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+            Some(libc::EAGAIN) => ErrorKind::ArbitrationLoss,
+            Some(libc::EIO) | Some(libc::EPROTO) => ErrorKind::Bus,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl<T> i2c::ErrorType for I2c<T> {
+    type Error = I2cError;
+}
+
+impl<T: I2cTransport> i2c::I2c<SevenBitAddress> for I2c<T> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        // add_raw marks addresses beyond 7 bits as 10 bit
+        if address > 0x7F {
+            return Err(I2cError::AddressRangeError);
+        }
+
+        let addr = u16::from(address);
+        let messages = operation_messages(addr, 0, operations)?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(|err| I2cError::buffer(Some(addr), err))
+    }
+}
+
+impl<T: I2cTransport> i2c::I2c<TenBitAddress> for I2c<T> {
+    fn transaction(
+        &mut self,
+        address: TenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.require_func(Functionality::TEN_BIT_ADDR)
+            .map_err(IoctlError::from)?;
+        if address > 0b0011_1111_1111 {
+            return Err(I2cError::AddressRangeError);
+        }
+
+        // the flag is needed even for addresses that would fit in 7 bits
+        let messages = operation_messages(address, I2C_M_TEN, operations)?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(|err| I2cError::buffer(Some(address), err))
+    }
+}
+
+// every operation becomes one message of a single I2C_RDWR transfer
+fn operation_messages<'a>(
+    addr: u16,
+    flags: u16,
+    operations: &'a mut [Operation<'_>],
+) -> Result<I2cMessageBuffer<'a>, I2cError> {
+    operations.iter_mut().try_fold(
+        I2cMessageBuffer::new(),
+        |messages, operation| match operation {
+            Operation::Read(buffer) => messages.add_read(addr, flags, buffer),
+            Operation::Write(buffer) => messages.add_write(addr, flags, buffer),
+        },
+    )
+}
+
+#[test]
+fn test_error_kind() {
+    use embedded_hal::i2c::Error;
+
+    let err = std::io::Error::from_raw_os_error(libc::EREMOTEIO);
+    let err = I2cError::read(0x76, None, IoctlError::IoctlError(err));
+    assert_eq!(
+        err.kind(),
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+    );
+    let err = std::io::Error::from_raw_os_error(libc::ENXIO);
+    assert_eq!(
+        I2cError::buffer(Some(0x76), IoctlError::IoctlError(err)).kind(),
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)
+    );
+    assert_eq!(I2cError::AddressRangeError.kind(), ErrorKind::Other);
+}
+
+#[test]
+fn test_ten_bit_operations() {
+    let register = [0xD0];
+    let mut buffer = [0; 2];
+    let mut operations = [Operation::Write(&register), Operation::Read(&mut buffer)];
+    let messages = operation_messages(0x50, I2C_M_TEN, &mut operations).unwrap();
+
+    assert_eq!(messages.len(), 2);
+    for message in messages.buffer.iter() {
+        assert_eq!(message.flags & I2C_M_TEN, I2C_M_TEN);
+    }
+}
+
+#[test]
+fn test_seven_bit_range() {
+    let mock = crate::MockTransport::with_registers([(0xD0, 0x61)]);
+    let mut handle = I2c::from_transport(mock, 0x76).unwrap();
+    let mut id = [0];
+    i2c::I2c::<SevenBitAddress>::write_read(&mut handle, 0x76, &[0xD0], &mut id).unwrap();
+    assert_eq!(id, [0x61]);
+
+    let result = i2c::I2c::<SevenBitAddress>::write(&mut handle, 0x80, &[0xD0]);
+    assert!(matches!(result, Err(I2cError::AddressRangeError)));
+}
+
+// checks the BME680 chip ID register is 0x61
+#[test]
+fn test_write_read() {
+    let mut handle = I2c::open(0x76).unwrap();
+    let mut id = [0];
+    // the inherent write_read takes precedence over the trait method
+    i2c::I2c::write_read(&mut handle, 0x76u8, &[0xD0], &mut id).unwrap();
+    assert_eq!(id, [0x61]);
+}
@@ -1,196 +1,1532 @@
+#[cfg(feature = "tokio")]
+mod async_io;
+mod builder;
+mod bus;
+mod error;
 mod func;
+#[cfg(feature = "embedded-hal")]
+mod hal;
 mod messages;
+mod mock;
+mod mux;
+mod poll;
+mod registers;
+mod smbus;
+mod transport;
 
-pub use func::Functionality;
-use libc::{c_int, c_ulong, ioctl};
-use messages::{I2cMessageBuffer, I2cReadWriteData};
+pub use builder::{AccessMode, I2cBuilder};
+pub use bus::{I2cBus, I2cDevice};
+pub use error::{I2cError, IoctlError};
+pub use func::{Functionality, Operation};
+use libc::{c_int, c_ulong, c_void};
 pub use messages::{
+    I2cMessage, I2cMessageBuffer, I2cReadWriteData, I2cSmbusIoctlData, MessageFlags,
     I2C_M_IGNORE_NACK, I2C_M_NOSTART, I2C_M_NO_RD_ACK, I2C_M_RD, I2C_M_RECV_LEN,
     I2C_M_REV_DIR_ADDR, I2C_M_TEN,
 };
+use messages::{
+    I2cSmbusData, I2C_SMBUS_BLOCK_DATA, I2C_SMBUS_BLOCK_MAX, I2C_SMBUS_BLOCK_PROC_CALL,
+    I2C_SMBUS_BYTE, I2C_SMBUS_BYTE_DATA, I2C_SMBUS_I2C_BLOCK_DATA, I2C_SMBUS_PROC_CALL,
+    I2C_SMBUS_QUICK, I2C_SMBUS_READ, I2C_SMBUS_WORD_DATA, I2C_SMBUS_WRITE,
+};
+pub use mock::MockTransport;
+pub use mux::{Mux, MuxGuard};
+pub use poll::{Poller, Readings};
+pub use registers::{ReadRegister, Register, RegisterValue, WriteRegister};
+pub use smbus::{FromSmbus, IntoSmbus};
 use std::convert::TryFrom;
-use std::os::unix::io::AsRawFd;
-use thiserror::Error;
+use std::mem::MaybeUninit;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+pub use transport::I2cTransport;
 
 // supported ioctl commands
+const I2C_RETRIES: c_ulong = 0x0701;
+const I2C_TIMEOUT: c_ulong = 0x0702;
+const I2C_SLAVE: c_ulong = 0x0703;
+const I2C_TENBIT: c_ulong = 0x0704;
 const I2C_FUNCS: c_ulong = 0x0705;
+const I2C_SLAVE_FORCE: c_ulong = 0x0706;
 const I2C_RDWR: c_ulong = 0x0707;
+const I2C_PEC: c_ulong = 0x0708;
+const I2C_SMBUS: c_ulong = 0x0720;
+
+// attempts at addressing a memory after writing a page, 1ms apart. most take
+// at most 5ms to store a page
+const PAGE_WRITE_POLLS: usize = 20;
+
+// longest sleep between retries of a transfer while the bus is busy
+const MAX_RETRY_DELAY: Duration = Duration::from_millis(64);
+
+// devices asserting SMBALERT# respond to a read from this address
+const SMBUS_ALERT_RESPONSE_ADDR: u16 = 0x0C;
 
 pub type I2cResult<T> = Result<T, I2cError>;
 
 #[derive(Debug)]
-pub struct I2c {
-    file: std::fs::File,
+pub struct I2c<T = std::fs::File> {
+    transport: T,
     addr: u16,
     func: Functionality,
+    // whether addr has been bound to the file descriptor with I2C_SLAVE
+    addr_bound: AtomicBool,
+    // bind with I2C_SLAVE_FORCE, even if a kernel driver owns the address
+    force: bool,
+    pec: AtomicBool,
+    // whether the descriptor has been put into 10 bit mode with I2C_TENBIT
+    ten_bit: AtomicBool,
+    // the kernel gives no way to read the retry count back, so it is only
+    // known once set through this handle
+    retries: Mutex<Option<u32>>,
+    access: AccessMode,
 }
 
 impl I2c {
     pub fn open(addr: u16) -> I2cResult<Self> {
-        let path = "/dev/i2c-1";
+        Self::open_on_bus(1, addr)
+    }
+
+    pub fn open_on_bus(bus: u8, addr: u16) -> I2cResult<Self> {
+        Self::open_path(format!("/dev/i2c-{}", bus), addr)
+    }
+
+    // WARNING:
+    // binds the address even if it is already in use by a kernel driver, which
+    // then has its transfers interleaved with ours and may have its state
+    // corrupted by them. only intended for debugging, e.g. inspecting a chip
+    // already claimed by a hwmon driver
+    pub fn open_forced(bus: u8, addr: u16) -> I2cResult<Self> {
+        let mut handle = Self::open_unprobed(format!("/dev/i2c-{}", bus), addr)?;
+        handle.force = true;
+        handle.check_responds()?;
+        Ok(handle)
+    }
+
+    pub fn open_path<P: AsRef<Path>>(path: P, addr: u16) -> I2cResult<Self> {
+        let handle = Self::open_unprobed(path, addr)?;
+        handle.check_responds()?;
+        Ok(handle)
+    }
+
+    // fails with the missing functionality before probing the address if the
+    // adapter cannot do everything in required
+    pub fn open_with_required(bus: u8, addr: u16, required: Functionality) -> I2cResult<Self> {
+        let handle = Self::open_unprobed(format!("/dev/i2c-{}", bus), addr)?;
+        handle.require_func(required).map_err(IoctlError::from)?;
+        handle.check_responds()?;
+        Ok(handle)
+    }
+
+    // the lowest numbered bus whose adapter supports everything in required.
+    // adapters that cannot be opened, e.g. for lack of permissions, are skipped.
+    // fails with NoMatchingBus if none of them supports it
+    pub fn find_bus_with(required: Functionality) -> I2cResult<u8> {
+        let mut buses: Vec<u8> = std::fs::read_dir("/dev")
+            .map_err(I2cError::FileError)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                name.to_str()?.strip_prefix("i2c-")?.parse().ok()
+            })
+            .collect();
+        buses.sort_unstable();
+
+        buses
+            .into_iter()
+            .find(
+                |bus| match Self::open_unprobed(format!("/dev/i2c-{}", bus), 0) {
+                    Ok(handle) => handle.func.contains(required),
+                    Err(_) => false,
+                },
+            )
+            .ok_or(I2cError::NoMatchingBus { required })
+    }
+
+    // probes every address on the bus in the same way as i2cdetect, returning
+    // those that respond. addresses that are not acknowledged are skipped, as
+    // are those claimed by a kernel driver, which fail to bind with EBUSY and
+    // show as UU in i2cdetect. any other failure is returned
+    pub fn scan_bus(bus: u8) -> I2cResult<Vec<u16>> {
+        let mut handle = Self::open_unprobed(format!("/dev/i2c-{}", bus), 0)?;
+        let mut found = Vec::new();
+        for addr in 0x03..=0x77 {
+            handle.set_addr_unchecked(addr);
+            match handle.ping() {
+                Ok(()) => found.push(addr),
+                Err(err) if is_unacknowledged(&err) => (),
+                Err(err) if err.raw_os_error() == Some(libc::EBUSY) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(found)
+    }
+
+    // opens the device and validates addr, without checking that it responds
+    pub(crate) fn open_unprobed<P: AsRef<Path>>(path: P, addr: u16) -> I2cResult<Self> {
+        Self::open_unprobed_with(path, addr, AccessMode::ReadWrite)
+    }
+
+    pub(crate) fn open_unprobed_with<P: AsRef<Path>>(
+        path: P,
+        addr: u16,
+        access: AccessMode,
+    ) -> I2cResult<Self> {
+        let path = path.as_ref();
+        // std opens every file with O_CLOEXEC, so the descriptor is not
+        // inherited by child processes unless I2cBuilder::close_on_exec says so
         let file = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
+            .read(access != AccessMode::WriteOnly)
+            .write(access != AccessMode::ReadOnly)
             .open(path)
-            .map_err(I2cError::FileError)?;
+            .map_err(I2cError::open)?;
 
-        let func = Self::get_func(file.as_raw_fd())?;
-        // address is too long for supported address range
-        if (!func._10_bit_addr() & (addr > 0b0111_1111))
-            | (func._10_bit_addr() & (addr > 0b0011_1111_1111))
-        {
-            return Err(I2cError::AddressRangeError);
+        // anything other than an i2c-dev node rejects I2C_FUNCS outright
+        let result = Self::from_file(file, addr).map(|handle| Self { access, ..handle });
+        match result {
+            Err(err) if err.raw_os_error() == Some(libc::ENOTTY) => Err(I2cError::NotAnI2cDevice {
+                path: path.to_path_buf(),
+            }),
+            result => result,
+        }
+    }
+
+    // takes over a file opened elsewhere, e.g. with particular flags or handed
+    // over by a broker. addr is validated but not probed
+    pub fn from_file(file: std::fs::File, addr: u16) -> I2cResult<Self> {
+        Self::from_transport(file, addr)
+    }
+
+    pub(crate) fn set_close_on_exec(&self, enabled: bool) -> I2cResult<()> {
+        let fd = self.transport.as_raw_fd();
+        // SAFETY:
+        // file descriptor guaranteed to point to valid open file
+        // F_GETFD and F_SETFD take no pointers
+        let flags =
+            get_err(unsafe { libc::fcntl(fd, libc::F_GETFD) }).map_err(I2cError::FileError)?;
+        let flags = match enabled {
+            true => flags | libc::FD_CLOEXEC,
+            false => flags & !libc::FD_CLOEXEC,
         };
+        get_err(unsafe { libc::fcntl(fd, libc::F_SETFD, flags) }).map_err(I2cError::FileError)?;
+        Ok(())
+    }
 
-        let handle = Self { file, addr, func };
+    pub(crate) fn set_nonblocking(&self, enabled: bool) -> I2cResult<()> {
+        let fd = self.transport.as_raw_fd();
+        // SAFETY:
+        // file descriptor guaranteed to point to valid open file
+        // F_GETFL and F_SETFL take no pointers
+        let flags =
+            get_err(unsafe { libc::fcntl(fd, libc::F_GETFL) }).map_err(I2cError::FileError)?;
+        let flags = match enabled {
+            true => flags | libc::O_NONBLOCK,
+            false => flags & !libc::O_NONBLOCK,
+        };
+        get_err(unsafe { libc::fcntl(fd, libc::F_SETFL, flags) }).map_err(I2cError::FileError)?;
+        Ok(())
+    }
 
-        // send single byte read request to test address
-        let mut buffer: u8 = 0;
-        let messages =
-            I2cMessageBuffer::new().add_raw(addr, messages::I2C_M_RD, 1, &mut buffer as *mut u8);
-        let data = I2cReadWriteData::from_messages(&messages);
-        i2c_rdwr_ioctl(&handle, &data).map_err(I2cError::AddressError)?;
+    // a second handle to the same device without querying its functionality
+    // again. unlike File::try_clone, the device is opened anew through
+    // /proc/self/fd rather than the descriptor duplicated: i2c-dev keeps the
+    // bound address per open file description, so with a shared one a
+    // set_addr on either handle would silently retarget the other's SMBUS
+    // transfers. this needs procfs, and permission to open the device node,
+    // which a descriptor handed over by a broker through from_file may lack.
+    // PEC, 10 bit mode, O_NONBLOCK and close-on-exec are per descriptor too,
+    // so are carried over, while retries and timeout belong to the adapter
+    // and are shared by every handle on the bus
+    pub fn try_clone(&self) -> I2cResult<Self> {
+        let fd = self.transport.as_raw_fd();
+        let file = std::fs::OpenOptions::new()
+            .read(self.access != AccessMode::WriteOnly)
+            .write(self.access != AccessMode::ReadOnly)
+            .open(format!("/proc/self/fd/{}", fd))
+            .map_err(I2cError::open)?;
+
+        let handle = Self {
+            transport: file,
+            addr: self.addr,
+            func: self.func,
+            addr_bound: AtomicBool::new(false),
+            force: self.force,
+            pec: AtomicBool::new(false),
+            ten_bit: AtomicBool::new(false),
+            retries: Mutex::new(self.retries()),
+            access: self.access,
+        };
+        if self.ten_bit.load(Ordering::Relaxed) {
+            handle.set_ten_bit(true)?;
+        }
+        if self.pec_enabled() {
+            handle.set_pec(true)?;
+        }
+        // SAFETY:
+        // file descriptor guaranteed to point to valid open file
+        // F_GETFL takes no pointers
+        let flags =
+            get_err(unsafe { libc::fcntl(fd, libc::F_GETFL) }).map_err(I2cError::FileError)?;
+        if flags & libc::O_NONBLOCK != 0 {
+            handle.set_nonblocking(true)?;
+        }
+        // SAFETY:
+        // file descriptor guaranteed to point to valid open file
+        // F_GETFD takes no pointers
+        let flags =
+            get_err(unsafe { libc::fcntl(fd, libc::F_GETFD) }).map_err(I2cError::FileError)?;
+        if flags & libc::FD_CLOEXEC == 0 {
+            handle.set_close_on_exec(false)?;
+        }
+        Ok(handle)
+    }
+
+    // the name the kernel gives the adapter, e.g. "bcm2835 (i2c@7e804000)".
+    // the minor number of an i2c-dev node is the number of its bus, so this
+    // works for handles opened by path too
+    pub fn adapter_name(&self) -> I2cResult<String> {
+        let metadata = self.transport.metadata().map_err(I2cError::FileError)?;
+        let bus = libc::minor(metadata.rdev());
+        let path = format!("/sys/class/i2c-dev/i2c-{}/name", bus);
+        let name = std::fs::read_to_string(path).map_err(I2cError::FileError)?;
+        Ok(name.trim_end().to_string())
+    }
+
+    /// Issues any ioctl on the descriptor, for commands not wrapped here. The
+    /// address is not bound first, and settings changed through it, e.g. with
+    /// I2C_SLAVE or I2C_PEC, are not reflected in the handle.
+    ///
+    /// # Safety
+    ///
+    /// arg must be of the type the kernel expects for request: a value for
+    /// commands taking one, otherwise a pointer to memory valid for reads and
+    /// writes of that type for the duration of the call.
+    pub unsafe fn ioctl_raw(&self, request: c_ulong, arg: *mut c_void) -> I2cResult<c_int> {
+        let code = libc::ioctl(self.transport.as_raw_fd(), request, arg);
+        Ok(get_err(code).map_err(IoctlError::from)?)
+    }
+}
+
+impl<T: I2cTransport> I2c<T> {
+    // a handle over any transport, e.g. a MockTransport for testing without
+    // hardware. addr is validated but not probed
+    pub fn from_transport(transport: T, addr: u16) -> I2cResult<Self> {
+        let func = transport.functionality().map_err(IoctlError::from)?;
+        check_addr_range(func, addr)?;
+
+        let handle = Self {
+            transport,
+            addr,
+            func,
+            addr_bound: AtomicBool::new(false),
+            force: false,
+            pec: AtomicBool::new(false),
+            ten_bit: AtomicBool::new(false),
+            retries: Mutex::new(None),
+            access: AccessMode::ReadWrite,
+        };
+        if addr > 0b0111_1111 {
+            handle.set_ten_bit(true)?;
+        }
 
         Ok(handle)
     }
 
+    // checks the device acknowledges its address without transferring any
+    // data, so no register is read or written
+    pub fn probe(&self) -> I2cResult<bool> {
+        let result = if self.func.smbus_quick() {
+            self.smbus_quick(false)
+        } else {
+            let messages = I2cMessageBuffer::new().add_write(self.addr, self.addr_flags(), &[])?;
+            let data = I2cReadWriteData::from_messages(&messages)?;
+            i2c_rdwr_ioctl(self, &data).map_err(I2cError::AddressError)
+        };
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(err) if is_unacknowledged(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn addr(&self) -> u16 {
+        self.addr
+    }
+
+    // true in 10 bit mode, which addresses beyond the 7 bit range always put
+    // the handle into
+    pub fn is_ten_bit(&self) -> bool {
+        self.ten_bit.load(Ordering::Relaxed)
+    }
+
     pub fn functionality(&self) -> &Functionality {
         &self.func
     }
 
+    // queries the functionality again, e.g. after the adapter behind the
+    // descriptor has changed. the address is not checked against it
+    pub fn refresh_functionality(&mut self) -> I2cResult<()> {
+        self.func = self.transport.functionality().map_err(IoctlError::from)?;
+        Ok(())
+    }
+
+    pub fn access_mode(&self) -> AccessMode {
+        self.access
+    }
+
     pub fn i2c_read_bytes(&self, register: u8, bytes: usize) -> I2cResult<Vec<u8>> {
+        let mut buffer = vec![0; bytes];
+        let messages = I2cMessageBuffer::new().add_read_reg(
+            self.addr,
+            self.addr_flags(),
+            &register,
+            &mut buffer[..],
+        )?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.read_error(Some(register)))?;
+        Ok(buffer)
+    }
+
+    // as i2c_read_bytes, also returning how long the transfer took. only the
+    // ioctl itself is timed, not building the messages
+    pub fn i2c_read_timed(&self, register: u8, bytes: usize) -> I2cResult<(Vec<u8>, Duration)> {
+        let mut buffer = vec![0; bytes];
+        let messages = I2cMessageBuffer::new().add_read_reg(
+            self.addr,
+            self.addr_flags(),
+            &register,
+            &mut buffer[..],
+        )?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        let start = std::time::Instant::now();
+        i2c_rdwr_ioctl(self, &data).map_err(self.read_error(Some(register)))?;
+        let elapsed = start.elapsed();
+        Ok((buffer, elapsed))
+    }
+
+    // writes the register, waits for delay, then reads in a separate
+    // transfer. for devices that start a conversion when the register is
+    // written and cannot take a repeated start until it completes
+    pub fn read_with_conversion_delay(
+        &self,
+        register: u8,
+        bytes: usize,
+        delay: Duration,
+    ) -> I2cResult<Vec<u8>> {
+        let messages = I2cMessageBuffer::new().add_write(
+            self.addr,
+            self.addr_flags(),
+            std::slice::from_ref(&register),
+        )?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.write_error(Some(register)))?;
+
+        std::thread::sleep(delay);
+
         let mut buffer = vec![0; bytes];
         let messages =
-            I2cMessageBuffer::new().add_read_reg(self.addr, 0, &register, &mut buffer[..]);
-        let data = I2cReadWriteData::from_messages(&messages);
-        i2c_rdwr_ioctl(&self, &data).map_err(I2cError::ReadError)?;
+            I2cMessageBuffer::new().add_read(self.addr, self.addr_flags(), &mut buffer)?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.read_error(Some(register)))?;
         Ok(buffer)
     }
 
-    pub fn i2c_read(&self, register: u8, buffer: &mut [u8]) -> I2cResult<()> {
-        let messages = I2cMessageBuffer::new().add_read_reg(self.addr, 0, &register, buffer);
-        let data = I2cReadWriteData::from_messages(&messages);
-        i2c_rdwr_ioctl(&self, &data).map_err(I2cError::ReadError)?;
+    // as i2c_read, without needing buffer to be initialised first. returns
+    // buffer as initialised bytes once the transfer succeeds
+    pub fn i2c_read_uninit<'b>(
+        &self,
+        register: u8,
+        buffer: &'b mut [MaybeUninit<u8>],
+    ) -> I2cResult<&'b mut [u8]> {
+        if buffer.is_empty() {
+            return Err(I2cError::EmptyBuffer);
+        }
+        let len =
+            u16::try_from(buffer.len()).map_err(|_| I2cError::LengthError { len: buffer.len() })?;
+
+        let messages = I2cMessageBuffer::new().add_write(
+            self.addr,
+            self.addr_flags(),
+            std::slice::from_ref(&register),
+        )?;
+        // SAFETY:
+        // len is the length of buffer, which is borrowed mutably until the
+        // messages are dropped. MaybeUninit<u8> has the same layout as u8
+        let messages = unsafe {
+            messages.add_raw(
+                self.addr,
+                I2C_M_RD | self.addr_flags(),
+                len,
+                buffer.as_mut_ptr().cast(),
+            )
+        };
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.read_error(Some(register)))?;
+
+        // SAFETY:
+        // a successful transfer means every message completed, and the kernel
+        // copies all len bytes of a completed read message into buffer
+        // MaybeUninit<u8> is guaranteed to have the same layout as u8
+        Ok(unsafe { std::slice::from_raw_parts_mut(buffer.as_mut_ptr().cast(), buffer.len()) })
+    }
+
+    // writes an arbitrary command then reads into buffer after a repeated
+    // start, in a single transfer
+    pub fn write_read(&self, write: &[u8], read: &mut [u8]) -> I2cResult<()> {
+        let messages = I2cMessageBuffer::new()
+            .add_write(self.addr, self.addr_flags(), write)?
+            .add_read(self.addr, self.addr_flags(), read)?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.read_error(None))
+    }
+
+    // writes start_register then reads count bytes in a single transfer, relying
+    // on the device to auto-increment its register pointer. this is the same
+    // transfer as i2c_read_bytes, named for burst reads
+    pub fn i2c_read_range(&self, start_register: u8, count: usize) -> I2cResult<Vec<u8>> {
+        self.i2c_read_bytes(start_register, count)
+    }
+
+    // returns the number of bytes read, which is always the length of buffer
+    pub fn i2c_read(&self, register: u8, buffer: &mut [u8]) -> I2cResult<usize> {
+        let len = buffer.len();
+        let messages = I2cMessageBuffer::new().add_read_reg(
+            self.addr,
+            self.addr_flags(),
+            &register,
+            buffer,
+        )?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.read_error(Some(register)))?;
+        Ok(len)
+    }
+
+    // reads a block whose length the device sends as its first byte, which
+    // ends up in buffer[0] followed by the data. returns the number of data
+    // bytes. the kernel only writes back the data, not the message length, so
+    // the count is taken from buffer[0]. buffer must be at least 33 bytes long
+    // to fit the largest block a device may send
+    pub fn i2c_read_recv_len(&self, register: u8, buffer: &mut [u8]) -> I2cResult<usize> {
+        if let Some(first) = buffer.first_mut() {
+            *first = 1;
+        }
+        let messages = I2cMessageBuffer::new()
+            .add_write(
+                self.addr,
+                self.addr_flags(),
+                std::slice::from_ref(&register),
+            )?
+            .add_read(self.addr, I2C_M_RECV_LEN | self.addr_flags(), buffer)?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.read_error(Some(register)))?;
+        Ok(usize::from(buffer[0]))
+    }
+
+    // for devices addressed with 16 bit registers, such as larger EEPROMs
+    pub fn i2c_read_reg16(&self, register: u16, buffer: &mut [u8]) -> I2cResult<()> {
+        let register = register.to_be_bytes();
+        let messages = I2cMessageBuffer::new().add_read_reg16(
+            self.addr,
+            self.addr_flags(),
+            &register,
+            buffer,
+        )?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.read_error(None))?;
         Ok(())
     }
 
-    pub fn i2c_write(&self, register: u8, buffer: &[u8]) -> I2cResult<()> {
-        // need to create a new buffer as first byte of buffer passed must be the register
-        let mut new_buffer = Vec::with_capacity(buffer.len() + 1);
-        new_buffer.push(register);
+    pub fn i2c_write_reg16(&self, register: u16, buffer: &[u8]) -> I2cResult<()> {
+        // need to create a new buffer as the register must be sent first
+        let mut new_buffer = Vec::with_capacity(buffer.len() + 2);
+        new_buffer.extend_from_slice(&register.to_be_bytes());
         new_buffer.extend_from_slice(buffer);
 
-        let messages = I2cMessageBuffer::new().add_write(self.addr, 0, &new_buffer);
-        let data = I2cReadWriteData::from_messages(&messages);
-        i2c_rdwr_ioctl(&self, &data).map_err(I2cError::WriteError)
+        let messages =
+            I2cMessageBuffer::new().add_write(self.addr, self.addr_flags(), &new_buffer)?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.write_error(None))
+    }
+
+    pub fn i2c_write(&self, register: u8, buffer: &[u8]) -> I2cResult<()> {
+        let mut scratch = Vec::with_capacity(buffer.len() + 1);
+        self.i2c_write_into(register, buffer, &mut scratch)
+    }
+
+    // as i2c_write, but reuses scratch rather than allocating on every call
+    pub fn i2c_write_into(
+        &self,
+        register: u8,
+        buffer: &[u8],
+        scratch: &mut Vec<u8>,
+    ) -> I2cResult<()> {
+        // need to copy into scratch as first byte of buffer passed must be the register
+        scratch.clear();
+        scratch.push(register);
+        scratch.extend_from_slice(buffer);
+
+        let messages = I2cMessageBuffer::new().add_write(self.addr, self.addr_flags(), scratch)?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.write_error(Some(register)))
+    }
+
+    // as i2c_read, but reissues the transfer while the bus is busy or
+    // arbitration is lost, sleeping for twice as long after each attempt, up
+    // to 64ms. attempts of 0 is taken as 1. I2C_RETRIES only covers retries
+    // when addressing the device
+    pub fn i2c_read_retry(
+        &self,
+        register: u8,
+        buffer: &mut [u8],
+        attempts: u8,
+    ) -> I2cResult<usize> {
+        retry(attempts, || self.i2c_read(register, buffer))
+    }
+
+    pub fn i2c_write_retry(&self, register: u8, buffer: &[u8], attempts: u8) -> I2cResult<()> {
+        let mut scratch = Vec::with_capacity(buffer.len() + 1);
+        retry(attempts, || {
+            self.i2c_write_into(register, buffer, &mut scratch)
+        })
     }
 
-    pub fn i2c_buffer(&self) -> I2cBuffer {
+    // writes data to an EEPROM-style memory starting at start_addr, which is
+    // sent as addr_bytes (1 or 2) big-endian bytes ahead of each page. writes
+    // are split at page boundaries, as the memory would otherwise wrap within
+    // the page, and the device is polled after each page until it acknowledges
+    // again, as it ignores its address while storing the page. a page_size of
+    // 0 writes everything at once. returns the number of bytes written
+    pub fn write_paged(
+        &self,
+        start_addr: u16,
+        data: &[u8],
+        page_size: usize,
+        addr_bytes: u8,
+    ) -> I2cResult<usize> {
+        let end = usize::from(start_addr) + data.len();
+        match addr_bytes {
+            1 if end <= 0x100 => (),
+            2 if end <= 0x10000 => (),
+            _ => return Err(I2cError::AddressRangeError),
+        }
+
+        let mut scratch = Vec::with_capacity(page_size.min(data.len()) + 2);
+        let mut addr = usize::from(start_addr);
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            let len = match page_size {
+                0 => remaining.len(),
+                _ => (page_size - addr % page_size).min(remaining.len()),
+            };
+            let (page, rest) = remaining.split_at(len);
+
+            scratch.clear();
+            let addr_be = (addr as u16).to_be_bytes();
+            scratch.extend_from_slice(&addr_be[2 - usize::from(addr_bytes)..]);
+            scratch.extend_from_slice(page);
+            let messages =
+                I2cMessageBuffer::new().add_write(self.addr, self.addr_flags(), &scratch)?;
+            let data = I2cReadWriteData::from_messages(&messages)?;
+            i2c_rdwr_ioctl(self, &data).map_err(self.write_error(None))?;
+
+            // a device that is still busy after this fails the next write, so
+            // one that never acknowledges again fails the whole write
+            let mut ready = false;
+            for _ in 0..PAGE_WRITE_POLLS {
+                if self.probe()? {
+                    ready = true;
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            if !ready {
+                let err = std::io::Error::from_raw_os_error(libc::ETIMEDOUT);
+                return Err(self.write_error(None)(IoctlError::from(err)));
+            }
+
+            addr += len;
+            remaining = rest;
+        }
+
+        Ok(data.len())
+    }
+
+    // one write message per (register, value) pair, issued as a single transfer
+    // so nothing else on the bus can come between them
+    pub fn i2c_write_regs(&self, writes: &[(u8, u8)]) -> I2cResult<()> {
+        let writes: Vec<[u8; 2]> = writes
+            .iter()
+            .map(|&(register, value)| [register, value])
+            .collect();
+
+        let mut messages = I2cMessageBuffer::new();
+        for write in writes.iter() {
+            messages = messages.add_write(self.addr, self.addr_flags(), write)?;
+        }
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.write_error(None))
+    }
+
+    pub fn read_byte(&self, register: u8) -> I2cResult<u8> {
+        let mut value = [0];
+        self.i2c_read(register, &mut value)?;
+        Ok(value[0])
+    }
+
+    pub fn write_byte(&self, register: u8, value: u8) -> I2cResult<()> {
+        self.i2c_write(register, &[value])
+    }
+
+    // reads the register, writes back f applied to it and returns the new
+    // value. the read and write are separate transfers, so this is not atomic
+    // with respect to other users of the bus
+    pub fn update_register(&self, register: u8, f: impl FnOnce(u8) -> u8) -> I2cResult<u8> {
+        let value = f(self.read_byte(register)?);
+        self.write_byte(register, value)?;
+        Ok(value)
+    }
+
+    pub fn set_bits(&self, register: u8, mask: u8) -> I2cResult<u8> {
+        self.update_register(register, |value| value | mask)
+    }
+
+    pub fn clear_bits(&self, register: u8, mask: u8) -> I2cResult<u8> {
+        self.update_register(register, |value| value & !mask)
+    }
+
+    pub fn read_u16_be(&self, register: u8) -> I2cResult<u16> {
+        let mut buffer = [0; 2];
+        self.i2c_read(register, &mut buffer)?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+
+    pub fn read_u16_le(&self, register: u8) -> I2cResult<u16> {
+        let mut buffer = [0; 2];
+        self.i2c_read(register, &mut buffer)?;
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    pub fn read_u32_be(&self, register: u8) -> I2cResult<u32> {
+        let mut buffer = [0; 4];
+        self.i2c_read(register, &mut buffer)?;
+        Ok(u32::from_be_bytes(buffer))
+    }
+
+    pub fn read_u32_le(&self, register: u8) -> I2cResult<u32> {
+        let mut buffer = [0; 4];
+        self.i2c_read(register, &mut buffer)?;
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    pub fn write_u16_be(&self, register: u8, value: u16) -> I2cResult<()> {
+        self.i2c_write(register, &value.to_be_bytes())
+    }
+
+    pub fn write_u16_le(&self, register: u8, value: u16) -> I2cResult<()> {
+        self.i2c_write(register, &value.to_le_bytes())
+    }
+
+    pub fn write_u32_be(&self, register: u8, value: u32) -> I2cResult<()> {
+        self.i2c_write(register, &value.to_be_bytes())
+    }
+
+    pub fn write_u32_le(&self, register: u8, value: u32) -> I2cResult<()> {
+        self.i2c_write(register, &value.to_le_bytes())
+    }
+
+    // reads directly from the file descriptor with no register write first
+    pub fn read_raw(&self, buffer: &mut [u8]) -> I2cResult<usize> {
+        if self.access == AccessMode::WriteOnly {
+            return Err(I2cError::AccessDenied);
+        }
+        self.bind_addr().map_err(self.read_error(None))?;
+        self.transport
+            .read(buffer)
+            .map_err(|err| self.read_error(None)(err.into()))
+    }
+
+    // writes directly to the file descriptor as a single message
+    pub fn write_raw(&self, buffer: &[u8]) -> I2cResult<usize> {
+        if self.access == AccessMode::ReadOnly && !buffer.is_empty() {
+            return Err(I2cError::AccessDenied);
+        }
+        self.bind_addr().map_err(self.write_error(None))?;
+        self.transport
+            .write(buffer)
+            .map_err(|err| self.write_error(None)(err.into()))
+    }
+
+    // every transfer in this crate is synchronous: the ioctl or read/write call
+    // only returns once the adapter has finished the transfer on the bus, and
+    // i2c-dev does no buffering of its own. so this does nothing, and exists
+    // to make that guarantee explicit
+    pub fn flush(&self) -> I2cResult<()> {
+        Ok(())
+    }
+
+    // sends only the read/write bit, succeeding if the address is acknowledged
+    pub fn smbus_quick(&self, value: bool) -> I2cResult<()> {
+        let mut data = I2cSmbusData::new();
+        let args = I2cSmbusIoctlData::new(u8::from(value), 0, I2C_SMBUS_QUICK, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_QUICK, &args).map_err(I2cError::AddressError)
+    }
+
+    pub fn smbus_read_byte(&self) -> I2cResult<u8> {
+        let mut data = I2cSmbusData::new();
+        let args = I2cSmbusIoctlData::new(I2C_SMBUS_READ, 0, I2C_SMBUS_BYTE, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_READ_BYTE, &args)
+            .map_err(self.read_error(None))?;
+        Ok(data.byte())
+    }
+
+    pub fn smbus_write_byte(&self, value: u8) -> I2cResult<()> {
+        // the byte is sent in place of the command, no data is used
+        let mut data = I2cSmbusData::new();
+        let args = I2cSmbusIoctlData::new(I2C_SMBUS_WRITE, value, I2C_SMBUS_BYTE, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_WRITE_BYTE, &args)
+            .map_err(self.write_error(None))
+    }
+
+    // byte data or word data, depending on the width of V
+    pub fn smbus_read<V: FromSmbus>(&self, command: u8) -> I2cResult<V> {
+        V::read_from(self, command)
+    }
+
+    pub fn smbus_write<V: IntoSmbus>(&self, command: u8, value: V) -> I2cResult<()> {
+        value.write_to(self, command)
+    }
+
+    pub fn smbus_read_byte_data(&self, command: u8) -> I2cResult<u8> {
+        let mut data = I2cSmbusData::new();
+        let args = I2cSmbusIoctlData::new(I2C_SMBUS_READ, command, I2C_SMBUS_BYTE_DATA, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_READ_BYTE_DATA, &args)
+            .map_err(self.read_error(Some(command)))?;
+        Ok(data.byte())
+    }
+
+    pub fn smbus_write_byte_data(&self, command: u8, value: u8) -> I2cResult<()> {
+        let mut data = I2cSmbusData::from_byte(value);
+        let args = I2cSmbusIoctlData::new(I2C_SMBUS_WRITE, command, I2C_SMBUS_BYTE_DATA, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_WRITE_BYTE_DATA, &args)
+            .map_err(self.write_error(Some(command)))
+    }
+
+    // SMBUS words are little-endian on the wire, the kernel converts them
+    // to and from the native u16 held in the data union
+    pub fn smbus_read_word_data(&self, command: u8) -> I2cResult<u16> {
+        let mut data = I2cSmbusData::new();
+        let args = I2cSmbusIoctlData::new(I2C_SMBUS_READ, command, I2C_SMBUS_WORD_DATA, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_READ_WORD_DATA, &args)
+            .map_err(self.read_error(Some(command)))?;
+        Ok(data.word())
+    }
+
+    pub fn smbus_write_word_data(&self, command: u8, value: u16) -> I2cResult<()> {
+        let mut data = I2cSmbusData::from_word(value);
+        let args = I2cSmbusIoctlData::new(I2C_SMBUS_WRITE, command, I2C_SMBUS_WORD_DATA, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_WRITE_WORD_DATA, &args)
+            .map_err(self.write_error(Some(command)))
+    }
+
+    // writes a word then reads a word back in a single transaction
+    pub fn smbus_process_call(&self, command: u8, value: u16) -> I2cResult<u16> {
+        let mut data = I2cSmbusData::from_word(value);
+        let args = I2cSmbusIoctlData::new(I2C_SMBUS_WRITE, command, I2C_SMBUS_PROC_CALL, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_PROC_CALL, &args)
+            .map_err(self.read_error(Some(command)))?;
+        Ok(data.word())
+    }
+
+    pub fn smbus_read_block_data(&self, command: u8) -> I2cResult<Vec<u8>> {
+        let mut data = I2cSmbusData::new();
+        let args = I2cSmbusIoctlData::new(I2C_SMBUS_READ, command, I2C_SMBUS_BLOCK_DATA, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_READ_BLOCK_DATA, &args)
+            .map_err(self.read_error(Some(command)))?;
+        Ok(data.block().to_vec())
+    }
+
+    pub fn smbus_write_block_data(&self, command: u8, buffer: &[u8]) -> I2cResult<()> {
+        if buffer.len() > I2C_SMBUS_BLOCK_MAX {
+            return Err(I2cError::BlockLengthError { len: buffer.len() });
+        }
+
+        let mut data = I2cSmbusData::from_block(buffer);
+        let args =
+            I2cSmbusIoctlData::new(I2C_SMBUS_WRITE, command, I2C_SMBUS_BLOCK_DATA, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_WRITE_BLOCK_DATA, &args)
+            .map_err(self.write_error(Some(command)))
+    }
+
+    // writes a block then reads a block back in a single transaction, the
+    // two blocks combined may not exceed the 32 byte smbus maximum. the block
+    // written is checked before anything is sent, but the length of the reply
+    // is only known once the device has sent it, so a reply that takes the
+    // total over the limit fails after the transfer has happened
+    pub fn smbus_block_process_call(&self, command: u8, buffer: &[u8]) -> I2cResult<Vec<u8>> {
+        // at least one byte must be left for the response
+        if buffer.len() >= I2C_SMBUS_BLOCK_MAX {
+            return Err(I2cError::BlockLengthError { len: buffer.len() });
+        }
+
+        let mut data = I2cSmbusData::from_block(buffer);
+        let args = I2cSmbusIoctlData::new(
+            I2C_SMBUS_WRITE,
+            command,
+            I2C_SMBUS_BLOCK_PROC_CALL,
+            &mut data,
+        );
+        i2c_smbus_ioctl(self, Functionality::SMBUS_BLOCK_PROC_CALL, &args)
+            .map_err(self.read_error(Some(command)))?;
+
+        let response = data.block();
+        match buffer.len() + response.len() {
+            len if len > I2C_SMBUS_BLOCK_MAX => Err(I2cError::BlockLengthError { len }),
+            _ => Ok(response.to_vec()),
+        }
+    }
+
+    // reads the alert response address to find which device asserted
+    // SMBALERT#, returning its address and the low bit of its response, which
+    // some devices use as a status flag. None if no device is alerting. the
+    // alerting device stops asserting the line once it has responded, and if
+    // several are alerting the one with the lowest address responds first.
+    // this is independent of the address of the handle
+    pub fn smbus_alert_respond(&self) -> I2cResult<Option<(u16, u16)>> {
+        let mut response = [0];
+        let messages =
+            I2cMessageBuffer::new().add_read(SMBUS_ALERT_RESPONSE_ADDR, 0, &mut response)?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        match i2c_rdwr_ioctl(self, &data)
+            .map_err(|err| I2cError::read(SMBUS_ALERT_RESPONSE_ADDR, None, err))
+        {
+            Ok(()) => {}
+            Err(err) if is_unacknowledged(&err) => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        let addr = u16::from(response[0] >> 1);
+        let status = u16::from(response[0] & 1);
+        Ok(Some((addr, status)))
+    }
+
+    // once enabled the kernel appends and checks the PEC byte on every
+    // smbus transfer
+    pub fn set_pec(&self, enabled: bool) -> I2cResult<()> {
+        if enabled {
+            self.require_func(Functionality::SMBUS_PEC)
+                .map_err(IoctlError::from)?;
+        }
+
+        i2c_set_ioctl(self, I2C_PEC, c_ulong::from(enabled))?;
+        self.pec.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // the state last set through this handle, which the kernel cannot report
+    pub fn pec_enabled(&self) -> bool {
+        self.pec.load(Ordering::Relaxed)
+    }
+
+    // unlike smbus_read_block_data, no length byte is sent by the device, so
+    // exactly len bytes are read starting from command
+    pub fn read_i2c_block_data(&self, command: u8, len: u8) -> I2cResult<Vec<u8>> {
+        let len = usize::from(len);
+        if len > I2C_SMBUS_BLOCK_MAX {
+            return Err(I2cError::BlockLengthError { len });
+        }
+
+        // the kernel reads the requested length from the first byte of the block
+        let mut data = I2cSmbusData::from_block(&[0; I2C_SMBUS_BLOCK_MAX][..len]);
+        let args =
+            I2cSmbusIoctlData::new(I2C_SMBUS_READ, command, I2C_SMBUS_I2C_BLOCK_DATA, &mut data);
+        i2c_smbus_ioctl(self, Functionality::SMBUS_READ_BLOCK, &args)
+            .map_err(self.read_error(Some(command)))?;
+        Ok(data.block().to_vec())
+    }
+
+    // unlike smbus_write_block_data, no length byte is sent to the device
+    pub fn write_i2c_block_data(&self, command: u8, buffer: &[u8]) -> I2cResult<()> {
+        if buffer.len() > I2C_SMBUS_BLOCK_MAX {
+            return Err(I2cError::BlockLengthError { len: buffer.len() });
+        }
+
+        let mut data = I2cSmbusData::from_block(buffer);
+        let args = I2cSmbusIoctlData::new(
+            I2C_SMBUS_WRITE,
+            command,
+            I2C_SMBUS_I2C_BLOCK_DATA,
+            &mut data,
+        );
+        i2c_smbus_ioctl(self, Functionality::SMBUS_WRITE_BLOCK, &args)
+            .map_err(self.write_error(Some(command)))
+    }
+
+    // reads every register from 0x00 to 0xFF, as i2cdump does. plain i2c
+    // adapters read them in a single burst and SMBUS adapters in blocks of 32,
+    // both relying on the device to auto-increment its register pointer.
+    // otherwise each register is read on its own
+    pub fn dump_registers(&self) -> I2cResult<[u8; 256]> {
+        let mut registers = [0; 256];
+        if self.func.i2c() {
+            self.i2c_read(0, &mut registers)?;
+        } else if self.func.smbus_read_block() {
+            for (i, chunk) in registers.chunks_mut(I2C_SMBUS_BLOCK_MAX).enumerate() {
+                let command = (i * I2C_SMBUS_BLOCK_MAX) as u8;
+                let block = self.read_i2c_block_data(command, chunk.len() as u8)?;
+                // adapters may return fewer bytes than asked for
+                if block.len() != chunk.len() {
+                    return Err(I2cError::BlockLengthError { len: block.len() });
+                }
+                chunk.copy_from_slice(&block);
+            }
+        } else {
+            for (register, value) in (0..=u8::MAX).zip(registers.iter_mut()) {
+                *value = self.smbus_read_byte_data(register)?;
+            }
+        }
+        Ok(registers)
+    }
+
+    // sets how many times the adapter retries a transfer that is not acknowledged
+    pub fn set_retries(&self, retries: u32) -> I2cResult<()> {
+        let mut current = self.retries.lock().unwrap();
+        i2c_set_ioctl(self, I2C_RETRIES, c_ulong::from(retries))?;
+        *current = Some(retries);
+        Ok(())
+    }
+
+    pub fn retries(&self) -> Option<u32> {
+        *self.retries.lock().unwrap()
+    }
+
+    // the kernel takes the timeout in units of 10ms, so it is rounded up to
+    // the next multiple of 10ms
+    pub fn set_timeout(&self, timeout: Duration) -> I2cResult<()> {
+        let units = timeout.as_nanos().div_ceil(10_000_000);
+        if units == 0 || units > c_int::MAX as u128 {
+            return Err(I2cError::TimeoutRangeError);
+        }
+
+        i2c_set_ioctl(self, I2C_TIMEOUT, units as c_ulong)?;
+        Ok(())
+    }
+
+    pub fn i2c_buffer(&self) -> I2cBuffer<'_, T> {
         I2cBuffer {
             buffer: I2cMessageBuffer::new(),
             handle: self,
         }
     }
 
-    fn get_func(descriptor: c_int) -> Result<Functionality, IoctlError> {
-        let mut func = 0;
-        get_err(unsafe { ioctl(descriptor, I2C_FUNCS, &mut func) })?;
+    pub fn i2c_buffer_with_capacity(&self, capacity: usize) -> I2cBuffer<'_, T> {
+        I2cBuffer {
+            buffer: I2cMessageBuffer::with_capacity(capacity),
+            handle: self,
+        }
+    }
+
+    // reuses the allocation of a previous buffer for a new transaction. the
+    // old buffer is cleared, releasing the slices it borrowed, so a single
+    // allocation can be carried across e.g. loop iterations that each borrow
+    // fresh slices:
+    //
+    // let mut spare = handle.i2c_buffer();
+    // loop {
+    //     let mut data = [0; 2];
+    //     let buffer = handle.recycle_buffer(spare).add_read(0, &mut data)?;
+    //     buffer.execute()?;
+    //     spare = handle.recycle_buffer(buffer);
+    //     ...
+    // }
+    pub fn recycle_buffer(&self, buffer: I2cBuffer<'_, T>) -> I2cBuffer<'_, T> {
+        I2cBuffer {
+            buffer: buffer.buffer.recycle(),
+            handle: self,
+        }
+    }
+
+    // tests the address when opening. adapters with plain i2c support are
+    // sent a single byte read, or an empty write from write-only handles, as
+    // an I2C_RDWR message, which carries its own address and so also reaches
+    // addresses claimed by a kernel driver. SMBUS-only adapters cannot do a
+    // plain i2c read, so fall back to ping, which binds the address
+    pub(crate) fn check_responds(&self) -> I2cResult<()> {
+        if !self.func.i2c() {
+            return self.ping();
+        }
+
+        let mut buffer = [0];
+        let messages = match self.access {
+            AccessMode::WriteOnly => {
+                I2cMessageBuffer::new().add_write(self.addr, self.addr_flags(), &[])?
+            }
+            _ => I2cMessageBuffer::new().add_read(self.addr, self.addr_flags(), &mut buffer)?,
+        };
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(I2cError::AddressError)
+    }
+
+    // addresses the device in the same way as i2cdetect, reading rather than
+    // using a quick write in the ranges where the latter could toggle the
+    // write protection of an EEPROM. adapters without plain i2c support fall
+    // back to an SMBUS read byte
+    pub(crate) fn ping(&self) -> I2cResult<()> {
+        let mut data = I2cSmbusData::new();
+        let read = self.access != AccessMode::WriteOnly
+            && (matches!(self.addr, 0x30..=0x37 | 0x50..=0x5F) || !self.func.i2c());
+        if read && self.func.smbus_read_byte() {
+            let args = I2cSmbusIoctlData::new(I2C_SMBUS_READ, 0, I2C_SMBUS_BYTE, &mut data);
+            i2c_smbus_ioctl(self, Functionality::SMBUS_READ_BYTE, &args)
+                .map_err(I2cError::AddressError)
+        } else if !read && self.func.smbus_quick() {
+            let args = I2cSmbusIoctlData::new(I2C_SMBUS_WRITE, 0, I2C_SMBUS_QUICK, &mut data);
+            i2c_smbus_ioctl(self, Functionality::SMBUS_QUICK, &args).map_err(I2cError::AddressError)
+        } else if self.access == AccessMode::WriteOnly {
+            let messages = I2cMessageBuffer::new().add_write(self.addr, self.addr_flags(), &[])?;
+            let data = I2cReadWriteData::from_messages(&messages)?;
+            i2c_rdwr_ioctl(self, &data).map_err(I2cError::AddressError)
+        } else {
+            // send single byte read request to test address
+            let mut buffer = [0];
+            let messages =
+                I2cMessageBuffer::new().add_read(self.addr, self.addr_flags(), &mut buffer)?;
+            let data = I2cReadWriteData::from_messages(&messages)?;
+            i2c_rdwr_ioctl(self, &data).map_err(I2cError::AddressError)
+        }
+    }
+
+    // retargets the handle at another device on the same bus. as when opening,
+    // addresses beyond the 7 bit range are accepted if the adapter supports 10
+    // bit addresses, and put the handle into 10 bit mode. on failure the
+    // handle is left unchanged
+    pub fn set_addr(&mut self, addr: u16) -> I2cResult<()> {
+        check_addr_range(self.func, addr)?;
+        if addr > 0b0111_1111 && !self.is_ten_bit() {
+            self.set_ten_bit(true)?;
+        }
+        self.set_addr_unchecked(addr);
+        Ok(())
+    }
+
+    // puts the descriptor into 10 bit mode, which some adapters need before a
+    // 10 bit address can be bound for SMBUS transfers and plain reads and
+    // writes. I2C_RDWR ignores it, so the handle sets I2C_M_TEN on each of the
+    // messages it builds instead. cannot be disabled while the address is
+    // beyond the 7 bit range
+    pub fn set_ten_bit(&self, enabled: bool) -> I2cResult<()> {
+        if enabled {
+            self.require_func(Functionality::TEN_BIT_ADDR)
+                .map_err(IoctlError::from)?;
+        } else if self.addr > 0b0111_1111 {
+            return Err(I2cError::AddressRangeError);
+        }
+        i2c_set_ioctl(self, I2C_TENBIT, c_ulong::from(enabled))?;
+        self.ten_bit.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub(crate) fn set_addr_unchecked(&mut self, addr: u16) {
+        self.addr = addr;
+        *self.addr_bound.get_mut() = false;
+    }
+
+    // binds the address to the descriptor now rather than on first use, as
+    // the std::io::Read and Write implementations require
+    pub fn bind(&self) -> I2cResult<()> {
+        self.bind_addr()?;
+        Ok(())
+    }
+
+    pub fn is_bound(&self) -> bool {
+        self.addr_bound.load(Ordering::Relaxed)
+    }
 
-        Ok(Functionality(func))
+    // SMBUS ioctls and plain reads and writes use the address bound to the file
+    // descriptor rather than one passed per message, so the address is only
+    // bound on first use
+    fn bind_addr(&self) -> Result<(), IoctlError> {
+        if !self.addr_bound.load(Ordering::Relaxed) {
+            let request = if self.force {
+                I2C_SLAVE_FORCE
+            } else {
+                I2C_SLAVE
+            };
+            i2c_set_ioctl(self, request, c_ulong::from(self.addr))?;
+            self.addr_bound.store(true, Ordering::Relaxed);
+        }
+        Ok(())
     }
 
-    fn require_func(&self, func: c_ulong) -> Result<(), Functionality> {
-        let mask = !self.functionality().0 & func;
-        match mask {
-            0 => Ok(()),
-            mask => Err(Functionality(mask)),
+    // flags every message to the device needs, I2C_M_TEN in 10 bit mode
+    fn addr_flags(&self) -> u16 {
+        match self.is_ten_bit() {
+            true => I2C_M_TEN,
+            false => 0,
         }
     }
+
+    // attaches the device and register to a failed read or write
+    fn read_error(&self, register: Option<u8>) -> impl Fn(IoctlError) -> I2cError {
+        let addr = self.addr;
+        move |err| I2cError::read(addr, register, err)
+    }
+
+    fn write_error(&self, register: Option<u8>) -> impl Fn(IoctlError) -> I2cError {
+        let addr = self.addr;
+        move |err| I2cError::write(addr, register, err)
+    }
+
+    fn require_func(&self, func: Functionality) -> Result<(), Functionality> {
+        match func.difference(self.func) {
+            missing if missing.is_empty() => Ok(()),
+            missing => Err(missing),
+        }
+    }
+}
+
+impl<T: AsRawFd> AsRawFd for I2c<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.transport.as_raw_fd()
+    }
+}
+
+// streams bytes to and from the device with plain reads and writes, e.g. to
+// send a firmware image to a bootloader with io::copy. these use the address
+// bound to the descriptor, so fail with NotConnected until bind is called
+impl<T: I2cTransport> std::io::Read for I2c<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.require_bound()?;
+        self.read_raw(buf).map_err(io_error)
+    }
+}
+
+impl<T: I2cTransport> std::io::Write for I2c<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.require_bound()?;
+        self.write_raw(buf).map_err(io_error)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T> I2c<T> {
+    fn require_bound(&self) -> std::io::Result<()> {
+        match self.addr_bound.load(Ordering::Relaxed) {
+            true => Ok(()),
+            false => Err(std::io::ErrorKind::NotConnected.into()),
+        }
+    }
+}
+
+// passes on the error from the descriptor where there is one
+fn io_error(err: I2cError) -> std::io::Error {
+    match err {
+        I2cError::ReadError {
+            source: IoctlError::IoctlError(err),
+            ..
+        }
+        | I2cError::WriteError {
+            source: IoctlError::IoctlError(err),
+            ..
+        }
+        | I2cError::NoAcknowledge { source: err, .. }
+        | I2cError::BusError { source: err, .. } => err,
+        err => std::io::Error::other(err),
+    }
 }
 
 #[derive(Debug)]
-pub struct I2cBuffer<'a> {
+#[must_use = "queued messages are only transferred by execute"]
+pub struct I2cBuffer<'a, T = std::fs::File> {
     buffer: I2cMessageBuffer<'a>,
-    handle: &'a I2c,
+    handle: &'a I2c<T>,
 }
 
-impl<'a> I2cBuffer<'a> {
-    pub fn add_read(self, flags: u16, buffer: &'a mut [u8]) -> Self {
-        let buffer = self.buffer.add_read(self.handle.addr, flags, buffer);
-        Self {
+impl<'a, T: I2cTransport> I2cBuffer<'a, T> {
+    // number of messages queued for the next execute
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    // payload bytes of all queued messages, for keeping under an adapter's
+    // transfer size limit
+    pub fn total_bytes(&self) -> usize {
+        self.buffer.total_bytes()
+    }
+
+    // removes all queued messages but keeps the allocation. the slices passed
+    // to the removed messages stay borrowed for as long as this buffer lives,
+    // use I2c::recycle_buffer to queue messages on new slices instead
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    pub fn add_read(self, flags: u16, buffer: &'a mut [u8]) -> I2cResult<Self> {
+        let addr = self.handle.addr;
+        self.add_read_at(addr, flags, buffer)
+    }
+
+    pub fn add_write(self, flags: u16, buffer: &'a [u8]) -> I2cResult<Self> {
+        let addr = self.handle.addr;
+        self.add_write_at(addr, flags, buffer)
+    }
+
+    // as add_read and add_write, with the flags built as MessageFlags rather
+    // than or-ed together from the I2C_M_* constants
+    pub fn add_read_with(self, flags: MessageFlags, buffer: &'a mut [u8]) -> I2cResult<Self> {
+        self.add_read(flags.into(), buffer)
+    }
+
+    pub fn add_write_with(self, flags: MessageFlags, buffer: &'a [u8]) -> I2cResult<Self> {
+        self.add_write(flags.into(), buffer)
+    }
+
+    // as add_read, addressed to another device on the same bus. messages to
+    // different devices are still transferred together by execute, without
+    // anything else on the bus in between
+    pub fn add_read_at(self, addr: u16, flags: u16, buffer: &'a mut [u8]) -> I2cResult<Self> {
+        check_addr_range(self.handle.func, addr)?;
+        let flags = flags | self.handle.addr_flags();
+        let buffer = self.buffer.add_read(addr, flags, buffer)?;
+        Ok(Self {
             buffer,
             handle: self.handle,
-        }
+        })
     }
 
-    pub fn add_write(self, flags: u16, buffer: &'a [u8]) -> Self {
-        let buffer = self.buffer.add_write(self.handle.addr, flags, buffer);
-        Self {
+    pub fn add_write_at(self, addr: u16, flags: u16, buffer: &'a [u8]) -> I2cResult<Self> {
+        check_addr_range(self.handle.func, addr)?;
+        let flags = flags | self.handle.addr_flags();
+        let buffer = self.buffer.add_write(addr, flags, buffer)?;
+        Ok(Self {
             buffer,
             handle: self.handle,
+        })
+    }
+
+    // the device reports the number of bytes it sends in its first byte, which
+    // ends up in buffer[0] followed by the data. buffer must be at least 33
+    // bytes long to fit the largest block a device may send
+    pub fn add_recv_len_read(self, flags: u16, buffer: &'a mut [u8]) -> I2cResult<Self> {
+        // the kernel expects the first byte to hold the number of bytes read
+        // before the length is known, which is just the length byte itself
+        if let Some(first) = buffer.first_mut() {
+            *first = 1;
         }
+        self.add_read(flags | I2C_M_RECV_LEN, buffer)
     }
 
-    pub fn add_raw(self, flags: u16, buffer: &'a mut [u8]) -> Self {
-        let len = u16::try_from(buffer.len()).unwrap();
-        let buffer = buffer.as_mut_ptr();
-        let buffer = self.buffer.add_raw(self.handle.addr, flags, len, buffer);
-        Self {
+    // flags are passed through as given, including I2C_M_RD. the message
+    // always covers the whole of buffer, which with I2C_M_RECV_LEN is the
+    // capacity for the length byte and the block that follows it
+    pub fn add_raw(self, flags: u16, buffer: &'a mut [u8]) -> I2cResult<Self> {
+        messages::check_flags(flags)?;
+        let len = messages::message_len(buffer)?;
+        debug_assert!(usize::from(len) <= buffer.len());
+        // SAFETY: len is the length of buffer, which is borrowed mutably for 'a
+        let buffer = unsafe {
+            self.buffer
+                .add_raw(self.handle.addr, flags, len, buffer.as_mut_ptr())
+        };
+        Ok(Self {
             buffer,
             handle: self.handle,
-        }
+        })
     }
 
     pub fn execute(&self) -> I2cResult<()> {
-        let data = I2cReadWriteData::from_messages(&self.buffer);
-        i2c_rdwr_ioctl(&self.handle, &data).map_err(I2cError::BufferError)
+        let data = I2cReadWriteData::from_messages(&self.buffer)?;
+        // messages may address several devices, so none is attached to errors
+        i2c_rdwr_ioctl(self.handle, &data).map_err(|err| I2cError::buffer(None, err))
+    }
+
+    // after execute, the value read by the message at index, which must be a
+    // read of exactly the width of the value. the slice passed to add_read
+    // stays borrowed for as long as the buffer lives, so the bytes are copied
+    // out from here instead
+    pub fn read_u16_be(&self, index: usize) -> Option<u16> {
+        self.read_array(index).map(u16::from_be_bytes)
+    }
+
+    pub fn read_u16_le(&self, index: usize) -> Option<u16> {
+        self.read_array(index).map(u16::from_le_bytes)
+    }
+
+    pub fn read_u32_be(&self, index: usize) -> Option<u32> {
+        self.read_array(index).map(u32::from_be_bytes)
+    }
+
+    pub fn read_u32_le(&self, index: usize) -> Option<u32> {
+        self.read_array(index).map(u32::from_le_bytes)
+    }
+
+    fn read_array<const N: usize>(&self, index: usize) -> Option<[u8; N]> {
+        let message = self.buffer.messages().get(index)?;
+        if !message.is_read() || usize::from(message.len) != N {
+            return None;
+        }
+
+        let mut bytes = [0; N];
+        // SAFETY:
+        // read messages are only queued on slices of len bytes that are
+        // borrowed mutably by the buffer, and the buffer is not Sync, so
+        // nothing can write to them during the copy
+        bytes.copy_from_slice(unsafe { std::slice::from_raw_parts(message.buffer, N) });
+        Some(bytes)
     }
 }
 
-#[derive(Debug, Error)]
-pub enum IoctlError {
-    #[error("missing functionality required for ioctl call")]
-    FunctionalityError(Functionality),
-    #[error(transparent)]
-    IoctlError(#[from] std::io::Error),
+impl<T> std::fmt::Display for I2cBuffer<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.buffer)
+    }
 }
 
-impl std::convert::From<Functionality> for IoctlError {
-    fn from(arg: Functionality) -> Self {
-        Self::FunctionalityError(arg)
+fn i2c_rdwr_ioctl<T: I2cTransport>(
+    handle: &I2c<T>,
+    data: &I2cReadWriteData,
+) -> Result<(), IoctlError> {
+    handle.require_func(Functionality::I2C)?;
+    check_access(handle.access, data.messages())?;
+    let result = handle.transport.rdwr(data);
+
+    #[cfg(feature = "tracing")]
+    trace_transfer(data, &result);
+
+    // the kernel returns the number of messages transferred, which can fall
+    // short of those queued without any error being reported
+    let completed = result?;
+    let total = data.messages().len();
+    if completed != total {
+        return Err(IoctlError::PartialTransfer { completed, total });
     }
+    Ok(())
 }
 
-#[derive(Debug, Error)]
-pub enum I2cError {
-    #[error("failed to open i2c device")]
-    FileError(#[source] std::io::Error),
-    #[error("failed on i2c read request")]
-    ReadError(#[source] IoctlError),
-    #[error("failed on i2c write request")]
-    WriteError(#[source] IoctlError),
-    #[error("failed on i2c buffer execute")]
-    BufferError(#[source] IoctlError),
-    #[error("i2c address unresponsive")]
-    AddressError(#[source] IoctlError),
-    #[error("address too long for supported address range")]
-    AddressRangeError,
-    #[error(transparent)]
-    IoctlError(#[from] IoctlError),
+// one event per I2C_RDWR transfer, enabled with e.g. RUST_LOG=i2c=trace
+#[cfg(feature = "tracing")]
+fn trace_transfer(data: &I2cReadWriteData, result: &std::io::Result<usize>) {
+    let messages = data.messages();
+    let addr = messages.first().map(|message| message.addr);
+    let bytes: usize = messages.iter().map(|message| message.len as usize).sum();
+
+    match result {
+        Ok(completed) => tracing::trace!(
+            target: "i2c",
+            ?addr,
+            messages = messages.len(),
+            bytes,
+            completed,
+            "transfer ok"
+        ),
+        Err(e) => tracing::trace!(
+            target: "i2c",
+            ?addr,
+            messages = messages.len(),
+            bytes,
+            errno = e.raw_os_error(),
+            "transfer failed"
+        ),
+    }
 }
 
-fn i2c_rdwr_ioctl(handle: &I2c, data: &I2cReadWriteData) -> Result<(), IoctlError> {
-    handle.require_func(func::I2C_FUNC_I2C)?;
+fn i2c_smbus_ioctl<T: I2cTransport>(
+    handle: &I2c<T>,
+    func: Functionality,
+    data: &I2cSmbusIoctlData,
+) -> Result<(), IoctlError> {
+    // SMBUS transfers only need their own functionality bit, not I2C_FUNC_I2C
+    handle.require_func(func)?;
+    let denied = match handle.access {
+        AccessMode::ReadOnly => data.writes(),
+        AccessMode::WriteOnly => data.reads(),
+        AccessMode::ReadWrite => false,
+    };
+    if denied {
+        return Err(IoctlError::AccessDenied);
+    }
+    handle.bind_addr()?;
+    handle.transport.smbus(data)?;
+    Ok(())
+}
 
-    // SAFETY:
-    // file descriptor guaranteed to point to valid open file
-    // data guaranteed to outlast function call
-    // parameters correctly passed as described in i2c.h and i2c-dev.h
-    // hope ioctl implementation doesn't mess things up
-    get_err(unsafe { ioctl(handle.file.as_raw_fd(), I2C_RDWR, data) })?;
+// the kernel allows any transfer through I2C_RDWR whatever mode the device was
+// opened in, so the access mode is enforced here instead. a write followed by a
+// read only selects what is read, and zero-length writes carry no data
+fn check_access(access: AccessMode, messages: &[I2cMessage]) -> Result<(), IoctlError> {
+    let allowed = match access {
+        AccessMode::ReadOnly => messages.iter().enumerate().all(|(i, message)| {
+            let selects = messages.get(i + 1).is_some_and(I2cMessage::is_read);
+            message.is_read() || message.is_empty() || selects
+        }),
+        AccessMode::WriteOnly => !messages.iter().any(I2cMessage::is_read),
+        AccessMode::ReadWrite => true,
+    };
+    match allowed {
+        true => Ok(()),
+        false => Err(IoctlError::AccessDenied),
+    }
+}
+
+// issues ioctl commands that take their argument by value
+fn i2c_set_ioctl<T: I2cTransport>(
+    handle: &I2c<T>,
+    request: c_ulong,
+    value: c_ulong,
+) -> Result<(), IoctlError> {
+    handle.transport.set(request, value)?;
     Ok(())
 }
 
+// the kernel reports addresses that do not acknowledge with either of these,
+// depending on the adapter
+fn is_unacknowledged(err: &I2cError) -> bool {
+    match err {
+        I2cError::NoAcknowledge { .. } => true,
+        I2cError::AddressError(_) => matches!(
+            err.raw_os_error(),
+            Some(libc::ENXIO) | Some(libc::EREMOTEIO)
+        ),
+        _ => false,
+    }
+}
+
+// runs f up to attempts times while it fails with EAGAIN or EBUSY, and always
+// at least once
+fn retry<T, F>(attempts: u8, mut f: F) -> I2cResult<T>
+where
+    F: FnMut() -> I2cResult<T>,
+{
+    let mut delay = Duration::from_millis(1);
+    for _ in 1..attempts.max(1) {
+        match f() {
+            Err(err) if matches!(err.raw_os_error(), Some(libc::EAGAIN) | Some(libc::EBUSY)) => {
+                std::thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+    f()
+}
+
+fn check_addr_range(func: Functionality, addr: u16) -> I2cResult<()> {
+    match func.validates_address(addr) {
+        true => Ok(()),
+        false => Err(I2cError::AddressRangeError),
+    }
+}
+
 // wraps ioctl calls to map its return into a Result
 fn get_err(code: c_int) -> Result<c_int, std::io::Error> {
     match code {
@@ -203,13 +1539,20 @@ fn get_err(code: c_int) -> Result<c_int, std::io::Error> {
 fn test_require_funcs() {
     let mut handle = I2c::open(0x76).unwrap();
     handle.func = Functionality::new(0b10110);
-    let result = handle.require_func(0b00100);
+    let result = handle.require_func(Functionality::new(0b00100));
     assert_eq!(result, Ok(()));
-    let result = handle.require_func(0b11001);
+    let result = handle.require_func(Functionality::new(0b11001));
     assert_eq!(result, Err(Functionality::new(0b01001)));
 }
 
 // these tests require that a BME680 chip is connected to the I2C bus
+#[test]
+fn test_smbus_only_functionality() {
+    let mut handle = I2c::open(0x76).unwrap();
+    handle.func = Functionality::SMBUS_READ_BYTE_DATA | Functionality::SMBUS_WRITE_BYTE_DATA;
+    assert_eq!(handle.smbus_read_byte_data(0xD0).unwrap(), 0x61);
+}
+
 // checks the BME680 chip ID register is 0x61
 #[test]
 fn test_i2c_read() {
@@ -221,8 +1564,29 @@ fn test_i2c_read() {
     assert_eq!(buffer, [0x61]);
 }
 
+// the BME680 chip ID register 0xD0 is followed by the reserved 0xD1
+#[test]
+fn test_i2c_read_range() {
+    let handle = I2c::open(0x76).unwrap();
+    let range = handle.i2c_read_range(0xD0, 2).unwrap();
+    assert_eq!(range.len(), 2);
+    assert_eq!(range[0], 0x61);
+}
+
+// the BME680 chip ID register 0xD0 is followed by the reserved 0xD1
+#[test]
+fn test_read_u16() {
+    let handle = I2c::open(0x76).unwrap();
+    let be = handle.read_u16_be(0xD0).unwrap();
+    let le = handle.read_u16_le(0xD0).unwrap();
+    assert_eq!(be >> 8, 0x61);
+    assert_eq!(le & 0xFF, 0x61);
+    assert_eq!(be, le.swap_bytes());
+}
+
 // checks the BME680 chip ID register is 0x61
 #[test]
+#[allow(clippy::useless_vec)]
 fn test_buffer_read() {
     let handle = I2c::open(0x76).unwrap();
     let mut data = vec![0xD0, 0];
@@ -231,7 +1595,9 @@ fn test_buffer_read() {
     handle
         .i2c_buffer()
         .add_write(0, register)
+        .unwrap()
         .add_read(0, id)
+        .unwrap()
         .execute()
         .unwrap();
 
@@ -249,18 +1615,92 @@ fn test_i2c_write() {
     assert_eq!(new_value.unwrap(), [1]);
 }
 
+#[test]
+fn test_i2c_write_regs() {
+    let handle = I2c::open(0x76).unwrap();
+    handle.i2c_write_regs(&[(0x72, 1), (0x74, 0)]).unwrap();
+    assert_eq!(handle.i2c_read_bytes(0x72, 1).unwrap(), [1]);
+    assert_eq!(handle.i2c_read_bytes(0x74, 1).unwrap(), [0]);
+
+    let result = handle.i2c_write_regs(&[(0x72, 1); 43]);
+    assert!(matches!(
+        result,
+        Err(I2cError::TooManyMessages { count: 43 })
+    ));
+}
+
+#[test]
+fn test_i2c_write_into() {
+    let handle = I2c::open(0x76).unwrap();
+    let address = 0x72;
+    let mut scratch = Vec::new();
+    handle.i2c_write_into(address, &[5], &mut scratch).unwrap();
+    handle.i2c_write_into(address, &[1], &mut scratch).unwrap();
+    let new_value = handle.i2c_read_bytes(address, 1);
+
+    assert_eq!(new_value.unwrap(), [1]);
+}
+
 #[test]
 fn test_buffer_write() {
     let handle = I2c::open(0x76).unwrap();
     let address = 0x72;
     let data = [address, 2];
 
-    handle.i2c_buffer().add_write(0, &data).execute().unwrap();
+    handle
+        .i2c_buffer()
+        .add_write(0, &data)
+        .unwrap()
+        .execute()
+        .unwrap();
     let new_value = handle.i2c_read_bytes(address, 1);
 
     assert_eq!(new_value.unwrap(), [2]);
 }
 
+// checks the BME680 chip ID register is 0x61
+#[test]
+fn test_raw_read_write() {
+    let handle = I2c::open(0x76).unwrap();
+    assert_eq!(handle.write_raw(&[0xD0]).unwrap(), 1);
+    let mut buffer = [0];
+    assert_eq!(handle.read_raw(&mut buffer).unwrap(), 1);
+    assert_eq!(buffer, [0x61]);
+}
+
+#[test]
+fn test_smbus_quick() {
+    let handle = I2c::open(0x76).unwrap();
+    handle.smbus_quick(false).unwrap();
+}
+
+// sets the BME680 register pointer to the chip ID register, then reads it
+#[test]
+fn test_smbus_byte() {
+    let handle = I2c::open(0x76).unwrap();
+    handle.smbus_write_byte(0xD0).unwrap();
+    assert_eq!(handle.smbus_read_byte().unwrap(), 0x61);
+}
+
+// checks the BME680 chip ID register is 0x61
+#[test]
+fn test_smbus_byte_data() {
+    let handle = I2c::open(0x76).unwrap();
+    assert_eq!(handle.smbus_read_byte_data(0xD0).unwrap(), 0x61);
+
+    let address = 0x72;
+    handle.smbus_write_byte_data(address, 3).unwrap();
+    assert_eq!(handle.smbus_read_byte_data(address).unwrap(), 3);
+}
+
+// the chip ID register is sent first, so it must be the low byte of the word
+#[test]
+fn test_smbus_word_data() {
+    let handle = I2c::open(0x76).unwrap();
+    let word = handle.smbus_read_word_data(0xD0).unwrap();
+    assert_eq!(word & 0xFF, 0x61);
+}
+
 #[test]
 fn test_bad_functionality() {
     use std::error::Error;
@@ -278,6 +1718,72 @@ fn test_bad_functionality() {
     );
 }
 
+// checks the BME680 chip ID register is 0x61
+#[test]
+fn test_i2c_block_data() {
+    let handle = I2c::open(0x76).unwrap();
+    assert_eq!(handle.read_i2c_block_data(0xD0, 1).unwrap(), vec![0x61]);
+
+    let address = 0x72;
+    handle.write_i2c_block_data(address, &[4]).unwrap();
+    assert_eq!(handle.read_i2c_block_data(address, 1).unwrap(), vec![4]);
+}
+
+#[test]
+fn test_missing_pec() {
+    let mut handle = I2c::open(0x76).unwrap();
+    handle.func = Functionality::new(0);
+    let result = handle.set_pec(true).unwrap_err();
+    assert_eq!(
+        format!("{}", result),
+        "missing functionality required for ioctl call"
+    );
+}
+
+#[test]
+fn test_retries() {
+    let handle = I2c::open(0x76).unwrap();
+    assert_eq!(handle.retries(), None);
+    handle.set_retries(3).unwrap();
+    assert_eq!(handle.retries(), Some(3));
+}
+
+#[test]
+fn test_bad_timeout() {
+    let handle = I2c::open(0x76).unwrap();
+    handle.set_timeout(Duration::from_millis(25)).unwrap();
+
+    let result = handle.set_timeout(Duration::from_secs(0)).unwrap_err();
+    assert_eq!(format!("{}", result), "timeout outside the supported range");
+    let result = handle
+        .set_timeout(Duration::from_secs(u64::MAX))
+        .unwrap_err();
+    assert_eq!(format!("{}", result), "timeout outside the supported range");
+}
+
+#[test]
+fn test_block_too_long() {
+    let handle = I2c::open(0x76).unwrap();
+    let data = [0; 33];
+    let result = handle.smbus_write_block_data(0x72, &data).unwrap_err();
+    assert_eq!(
+        format!("{}", result),
+        "block of 33 bytes exceeds the smbus maximum of 32"
+    );
+}
+
+#[test]
+fn test_probe() {
+    let handle = I2c::open(0x76).unwrap();
+    assert!(handle.probe().unwrap());
+}
+
+#[test]
+fn test_scan_bus() {
+    let found = I2c::scan_bus(1).unwrap();
+    assert!(found.contains(&0x76));
+}
+
 #[test]
 fn test_bad_addr() {
     use std::error::Error;
@@ -286,7 +1792,7 @@ fn test_bad_addr() {
     assert_eq!(format!("{}", result), "i2c address unresponsive");
     assert_eq!(
         format!("{}", result.source().unwrap()),
-        "Remote I/O error (os error 121)"
+        "device did not acknowledge"
     );
 
     let handle = I2c::open(0xFFFF).unwrap_err();
@@ -295,3 +1801,159 @@ fn test_bad_addr() {
         "address too long for supported address range"
     );
 }
+
+#[test]
+fn test_classify_errors() {
+    let err = std::io::Error::from_raw_os_error(libc::EREMOTEIO);
+    let result = I2cError::read(0x76, None, IoctlError::IoctlError(err));
+    assert!(matches!(
+        result,
+        I2cError::NoAcknowledge {
+            addr: Some(0x76),
+            register: None,
+            ..
+        }
+    ));
+    assert!(is_unacknowledged(&result));
+
+    let err = std::io::Error::from_raw_os_error(libc::EIO);
+    let result = I2cError::write(0x76, Some(0x72), IoctlError::IoctlError(err));
+    assert!(matches!(
+        result,
+        I2cError::BusError {
+            addr: Some(0x76),
+            register: Some(0x72),
+            ..
+        }
+    ));
+
+    let err = std::io::Error::from_raw_os_error(libc::EINVAL);
+    let result = I2cError::buffer(None, IoctlError::IoctlError(err));
+    assert!(matches!(result, I2cError::BufferError(_)));
+}
+
+#[test]
+fn test_retry() {
+    let mut calls = 0;
+    let result: I2cResult<()> = retry(3, || {
+        calls += 1;
+        let err = std::io::Error::from_raw_os_error(libc::EBUSY);
+        Err(I2cError::read(0x76, None, IoctlError::IoctlError(err)))
+    });
+    assert!(result.is_err());
+    assert_eq!(calls, 3);
+
+    let mut calls = 0;
+    let result: I2cResult<()> = retry(3, || {
+        calls += 1;
+        let err = std::io::Error::from_raw_os_error(libc::EREMOTEIO);
+        Err(I2cError::read(0x76, None, IoctlError::IoctlError(err)))
+    });
+    assert!(matches!(result, Err(I2cError::NoAcknowledge { .. })));
+    assert_eq!(calls, 1);
+    let mut calls = 0;
+    let result: I2cResult<()> = retry(0, || {
+        calls += 1;
+        Ok(())
+    });
+    assert!(result.is_ok());
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn test_open_with_required() {
+    let required = Functionality::I2C;
+    let handle = I2c::open_with_required(1, 0x76, required).unwrap();
+    assert!(handle.functionality().contains(required));
+
+    let result = I2c::open_with_required(1, 0x76, Functionality::all()).unwrap_err();
+    match result {
+        I2cError::IoctlError(IoctlError::FunctionalityError(missing)) => {
+            assert!(!missing.is_empty());
+            assert!(!handle.functionality().intersects(missing));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn test_try_clone() {
+    let handle = I2c::open(0x76).unwrap();
+    let clone = handle.try_clone().unwrap();
+    assert_ne!(handle.as_raw_fd(), clone.as_raw_fd());
+    assert_eq!(clone.functionality(), handle.functionality());
+    assert_eq!(clone.i2c_read_bytes(0xD0, 1).unwrap(), [0x61]);
+
+    // binding another address through the clone leaves the original bound
+    // to its own, so SMBUS transfers keep reaching the right device
+    let mut clone = handle.try_clone().unwrap();
+    assert_eq!(handle.smbus_read_byte_data(0xD0).unwrap(), 0x61);
+    clone.set_addr(0x77).unwrap();
+    clone.bind().unwrap();
+    assert!(clone.smbus_read_byte_data(0xD0).is_err());
+    assert_eq!(handle.smbus_read_byte_data(0xD0).unwrap(), 0x61);
+    clone.set_addr(0x76).unwrap();
+    assert_eq!(clone.smbus_read_byte_data(0xD0).unwrap(), 0x61);
+    assert_eq!(handle.smbus_read_byte_data(0xD0).unwrap(), 0x61);
+
+    handle.set_close_on_exec(false).unwrap();
+    let clone = handle.try_clone().unwrap();
+    let flags = unsafe { libc::fcntl(clone.as_raw_fd(), libc::F_GETFD) };
+    assert_eq!(flags & libc::FD_CLOEXEC, 0);
+}
+
+#[test]
+fn test_find_bus_with() {
+    assert_eq!(I2c::find_bus_with(Functionality::I2C).unwrap(), 1);
+
+    let result = I2c::find_bus_with(Functionality::all()).unwrap_err();
+    assert!(matches!(result, I2cError::NoMatchingBus { .. }));
+}
+
+#[test]
+fn test_missing_bus() {
+    use std::error::Error;
+
+    let result = I2c::open_on_bus(255, 0x76).unwrap_err();
+    assert_eq!(format!("{}", result), "failed to open i2c device");
+    assert_eq!(
+        format!("{}", result.source().unwrap()),
+        "No such file or directory (os error 2)"
+    );
+}
+
+#[test]
+fn test_not_an_i2c_device() {
+    let result = I2c::open_path("/dev/null", 0x76).unwrap_err();
+    assert!(matches!(result, I2cError::NotAnI2cDevice { .. }));
+    assert_eq!(format!("{}", result), "/dev/null is not an i2c device");
+}
+
+#[test]
+fn test_from_file() {
+    let file = std::fs::File::open("/dev/null").unwrap();
+    let result = I2c::from_file(file, 0x76).unwrap_err();
+    assert_eq!(result.raw_os_error(), Some(libc::ENOTTY));
+}
+
+#[test]
+fn test_missing_path() {
+    let result = I2c::open_path("/dev/i2c-missing", 0x76).unwrap_err();
+    assert!(matches!(result, I2cError::FileError(_)));
+}
+
+#[test]
+fn test_ioctl_raw() {
+    let handle = I2c::open(0x76).unwrap();
+    let mut func: c_ulong = 0;
+    let arg = &mut func as *mut c_ulong as *mut c_void;
+    // SAFETY: I2C_FUNCS writes a single unsigned long through the pointer
+    unsafe { handle.ioctl_raw(I2C_FUNCS, arg) }.unwrap();
+    assert_eq!(Functionality(func), *handle.functionality());
+}
+
+#[test]
+fn test_adapter_name() {
+    let handle = I2c::open(0x76).unwrap();
+    assert!(!handle.adapter_name().unwrap().is_empty());
+}
@@ -1,3 +1,4 @@
+use crate::{I2cError, I2cResult};
 use std::convert::TryFrom;
 
 // I2C message flags
@@ -9,6 +10,73 @@ pub const I2C_M_IGNORE_NACK: u16 = 0x1000;
 pub const I2C_M_REV_DIR_ADDR: u16 = 0x2000;
 pub const I2C_M_NOSTART: u16 = 0x4000;
 
+// names of the flags other than I2C_M_RD, in the order they are displayed
+const FLAG_NAMES: [(u16, &str); 6] = [
+    (I2C_M_TEN, "TEN"),
+    (I2C_M_RECV_LEN, "RECV_LEN"),
+    (I2C_M_NO_RD_ACK, "NO_RD_ACK"),
+    (I2C_M_IGNORE_NACK, "IGNORE_NACK"),
+    (I2C_M_REV_DIR_ADDR, "REV_DIR_ADDR"),
+    (I2C_M_NOSTART, "NOSTART"),
+];
+
+// flags that only apply to reads
+const READ_FLAGS: u16 = I2C_M_RD | I2C_M_RECV_LEN | I2C_M_NO_RD_ACK;
+
+// typed alternative to or-ing the I2C_M_* constants together. the direction is
+// left out, as it is set by whether the message is added as a read or a write
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct MessageFlags(u16);
+
+impl MessageFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn ten_bit(self) -> Self {
+        Self(self.0 | I2C_M_TEN)
+    }
+
+    pub fn no_read_ack(self) -> Self {
+        Self(self.0 | I2C_M_NO_RD_ACK)
+    }
+
+    pub fn ignore_nack(self) -> Self {
+        Self(self.0 | I2C_M_IGNORE_NACK)
+    }
+
+    pub fn rev_dir_addr(self) -> Self {
+        Self(self.0 | I2C_M_REV_DIR_ADDR)
+    }
+
+    pub fn nostart(self) -> Self {
+        Self(self.0 | I2C_M_NOSTART)
+    }
+}
+
+impl From<MessageFlags> for u16 {
+    fn from(flags: MessageFlags) -> Self {
+        flags.0
+    }
+}
+
+// SMBUS transaction directions and sizes
+pub const I2C_SMBUS_READ: u8 = 1;
+pub const I2C_SMBUS_WRITE: u8 = 0;
+pub const I2C_SMBUS_QUICK: u32 = 0;
+pub const I2C_SMBUS_BYTE: u32 = 1;
+pub const I2C_SMBUS_BYTE_DATA: u32 = 2;
+pub const I2C_SMBUS_WORD_DATA: u32 = 3;
+pub const I2C_SMBUS_PROC_CALL: u32 = 4;
+pub const I2C_SMBUS_BLOCK_DATA: u32 = 5;
+pub const I2C_SMBUS_BLOCK_PROC_CALL: u32 = 7;
+pub const I2C_SMBUS_I2C_BLOCK_DATA: u32 = 8;
+
+pub const I2C_SMBUS_BLOCK_MAX: usize = 32;
+
+// maximum number of messages in a single I2C_RDWR call, as defined in i2c-dev.h
+pub const I2C_RDWR_IOCTL_MAX_MSGS: usize = 42;
+
 // i2c_rdwr_ioctl_data struct, as defined in i2c-dev.h
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -20,25 +88,62 @@ pub struct I2cReadWriteData<'a> {
     _phantom: std::marker::PhantomData<&'a I2cMessage>,
 }
 
-// TODO handle buffer overflow case
 impl<'a> I2cReadWriteData<'a> {
-    pub fn from_messages(buffer: &'a I2cMessageBuffer) -> Self {
-        let messages = buffer.buffer.as_ptr();
-        let num = u32::try_from(buffer.buffer.len()).unwrap();
-        Self {
-            messages,
-            num,
-            _phantom: std::marker::PhantomData,
+    pub fn from_messages(buffer: &'a I2cMessageBuffer) -> I2cResult<Self> {
+        // SAFETY:
+        // the buffers of the messages are borrowed by the I2cMessageBuffer,
+        // which is itself borrowed for 'a
+        unsafe { Self::from_slice(&buffer.buffer) }
+    }
+
+    /// Transfers messages that did not come straight from an
+    /// I2cMessageBuffer, e.g. copied out of one with
+    /// I2cMessageBuffer::messages.
+    ///
+    /// # Safety
+    ///
+    /// The buffer of every message must be valid for reads of its len bytes
+    /// for 'a, and for writes too for read messages. Messages copied out of an
+    /// I2cMessageBuffer are only valid for as long as it lives.
+    pub unsafe fn from_slice(messages: &'a [I2cMessage]) -> I2cResult<Self> {
+        // the kernel rejects anything longer with a bare EINVAL
+        let count = messages.len();
+        if count > I2C_RDWR_IOCTL_MAX_MSGS {
+            return Err(I2cError::TooManyMessages { count });
+        }
+
+        if cfg!(debug_assertions) {
+            check_aliasing(messages)?;
         }
+
+        // count is at most 42 here, so the cast cannot truncate
+        Ok(Self {
+            messages: messages.as_ptr(),
+            num: count as u32,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    pub fn messages(&self) -> &'a [I2cMessage] {
+        // SAFETY:
+        // messages and num always come from a slice borrowed for 'a
+        unsafe { std::slice::from_raw_parts(self.messages, self.num as usize) }
     }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[must_use = "queued messages are only transferred once passed to an ioctl"]
 pub struct I2cMessageBuffer<'a> {
-    buffer: Vec<I2cMessage>,
+    pub(crate) buffer: Vec<I2cMessage>,
     _phantom: std::marker::PhantomData<&'a I2cMessage>,
 }
 
+impl Default for I2cMessageBuffer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> I2cMessageBuffer<'a> {
     pub fn new() -> Self {
         let buffer = Vec::new();
@@ -48,34 +153,148 @@ impl<'a> I2cMessageBuffer<'a> {
         }
     }
 
-    pub fn add_read(self, addr: u16, flags: u16, buffer: &'a mut [u8]) -> Self {
+    // room for capacity messages before reallocating. a single transfer holds
+    // at most 42 messages, so there is no point in reserving more
+    pub fn with_capacity(capacity: usize) -> Self {
+        let buffer = Vec::with_capacity(capacity);
+        Self {
+            buffer,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    // the queued messages, for inspecting a transaction without issuing it
+    pub fn messages(&self) -> &[I2cMessage] {
+        &self.buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    // the messages as a pointer to an i2c_msg array and its length, for
+    // handing to C code. the pointer is only valid while the buffer is
+    // neither modified nor dropped, and the message buffers it points to are
+    // borrowed for 'a. dereferencing either is up to the caller
+    pub fn as_raw_parts(&self) -> (*const I2cMessage, usize) {
+        (self.buffer.as_ptr(), self.buffer.len())
+    }
+
+    // bytes transferred across all queued messages, excluding addresses
+    pub fn total_bytes(&self) -> usize {
+        self.buffer.iter().map(|message| message.len as usize).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    // once cleared no message points into the old buffers, so the allocation
+    // can be handed on to messages with an unrelated lifetime
+    pub fn recycle<'b>(mut self) -> I2cMessageBuffer<'b> {
+        self.clear();
+        I2cMessageBuffer {
+            buffer: self.buffer,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    // moves the messages of other to the end of this buffer, so a transfer
+    // can be put together from parts built separately. fails without changing
+    // either buffer if the result would be too long for a single transfer
+    pub fn append(&mut self, mut other: I2cMessageBuffer<'a>) -> I2cResult<()> {
+        let count = self.buffer.len() + other.buffer.len();
+        if count > I2C_RDWR_IOCTL_MAX_MSGS {
+            return Err(I2cError::TooManyMessages { count });
+        }
+        self.buffer.append(&mut other.buffer);
+        Ok(())
+    }
+
+    // zero-length reads are rejected, as adapters handle them inconsistently.
+    // zero-length writes are allowed, as they are used to probe addresses
+    pub fn add_read(self, addr: u16, flags: u16, buffer: &'a mut [u8]) -> I2cResult<Self> {
+        if buffer.is_empty() {
+            return Err(I2cError::EmptyBuffer);
+        }
         let flags = flags | I2C_M_RD;
-        let len = u16::try_from(buffer.len()).unwrap();
-        let buffer = buffer.as_mut_ptr();
-        self.add_raw(addr, flags, len, buffer)
+        let len = message_len(buffer)?;
+        debug_assert!(usize::from(len) <= buffer.len());
+        // SAFETY: len is the length of buffer, which is borrowed mutably for 'a
+        Ok(unsafe { self.add_raw(addr, flags, len, buffer.as_mut_ptr()) })
     }
 
-    pub fn add_write(self, addr: u16, flags: u16, buffer: &'a [u8]) -> Self {
-        let flags = flags & !I2C_M_RD;
-        let len = u16::try_from(buffer.len()).unwrap();
+    // flags that only apply to reads are rejected, including I2C_M_RD itself
+    pub fn add_write(self, addr: u16, flags: u16, buffer: &'a [u8]) -> I2cResult<Self> {
+        if flags & READ_FLAGS != 0 {
+            return Err(I2cError::FlagError { flags });
+        }
+        let len = message_len(buffer)?;
+        debug_assert!(usize::from(len) <= buffer.len());
         // function guarantees I2C read flag never set, so buffer will never be written to
         let buffer = buffer.as_ptr() as *mut u8;
-        self.add_raw(addr, flags, len, buffer)
+        // SAFETY: len is the length of buffer, which is borrowed for 'a
+        Ok(unsafe { self.add_raw(addr, flags, len, buffer) })
     }
 
+    // writes the register, then reads from it after a repeated start. flags
+    // apply to both messages, except for the direction which is set per message
     pub fn add_read_reg(
         self,
         addr: u16,
         flags: u16,
         register: &'a u8,
         buffer: &'a mut [u8],
-    ) -> Self {
+    ) -> I2cResult<Self> {
         let register = std::slice::from_ref(register);
-        self.add_write(addr, flags, register)
-            .add_read(addr, flags, buffer)
+        self.add_read_reg_slice(addr, flags, register, buffer)
+    }
+
+    // as add_read_reg, for devices with two byte, big-endian register addresses
+    pub fn add_read_reg16(
+        self,
+        addr: u16,
+        flags: u16,
+        register: &'a [u8; 2],
+        buffer: &'a mut [u8],
+    ) -> I2cResult<Self> {
+        self.add_read_reg_slice(addr, flags, register, buffer)
     }
 
-    pub fn add_raw(mut self, addr: u16, flags: u16, len: u16, buffer: *mut u8) -> Self {
+    fn add_read_reg_slice(
+        self,
+        addr: u16,
+        flags: u16,
+        register: &'a [u8],
+        buffer: &'a mut [u8],
+    ) -> I2cResult<Self> {
+        let write_flags = flags & !READ_FLAGS;
+        let read_flags = flags | I2C_M_RD;
+        self.add_write(addr, write_flags, register)?
+            .add_read(addr, read_flags, buffer)
+    }
+
+    // SAFETY:
+    // buffer must be valid for reads of len bytes for 'a, and for writes too if
+    // flags has I2C_M_RD set. with I2C_M_RECV_LEN, len is the capacity of
+    // buffer rather than the number of bytes read, and must be enough for the
+    // length byte and the largest block the device may send
+    pub(crate) unsafe fn add_raw(
+        mut self,
+        addr: u16,
+        flags: u16,
+        len: u16,
+        buffer: *mut u8,
+    ) -> Self {
+        // addresses beyond the 7 bit range can only be sent as 10 bit addresses
+        let flags = match addr {
+            addr if addr > 0b0111_1111 => flags | I2C_M_TEN,
+            _ => flags,
+        };
         self.buffer.push(I2cMessage {
             addr,
             flags,
@@ -86,12 +305,457 @@ impl<'a> I2cMessageBuffer<'a> {
     }
 }
 
-// i2c_message struct as defined in i2c.h
+// I2C_M_RECV_LEN and I2C_M_NO_RD_ACK only apply to reads, so are rejected
+// without I2C_M_RD rather than being left to the kernel or adapter to reject
+// with a bare EINVAL or ignore
+pub(crate) fn check_flags(flags: u16) -> I2cResult<()> {
+    match flags & I2C_M_RD == 0 && flags & READ_FLAGS != 0 {
+        true => Err(I2cError::FlagError { flags }),
+        false => Ok(()),
+    }
+}
+
+// a read into memory that another message of the same transfer also reads
+// into or writes from leaves the result up to the order the adapter handles
+// them in. the borrows taken by I2cMessageBuffer rule this out, but messages
+// passed to I2cReadWriteData::from_slice are only checked here, in debug builds
+fn check_aliasing(messages: &[I2cMessage]) -> I2cResult<()> {
+    let range = |message: &I2cMessage| {
+        let start = message.buffer as usize;
+        start..start + usize::from(message.len)
+    };
+
+    for (i, read) in messages.iter().enumerate() {
+        if !read.is_read() || read.is_empty() {
+            continue;
+        }
+        let read_range = range(read);
+        for (j, other) in messages.iter().enumerate() {
+            let other_range = range(other);
+            let overlaps = read_range.start < other_range.end && other_range.start < read_range.end;
+            if i != j && overlaps {
+                return Err(I2cError::BufferAlias);
+            }
+        }
+    }
+    Ok(())
+}
+
+// message lengths are limited to u16 by the i2c_msg struct
+pub fn message_len(buffer: &[u8]) -> I2cResult<u16> {
+    u16::try_from(buffer.len()).map_err(|_| I2cError::LengthError { len: buffer.len() })
+}
+
+// i2c_msg struct as defined in i2c.h. repr(C) keeps the layout identical to
+// the C struct, so arrays of messages can be passed to and from C code as is
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct I2cMessage {
-    addr: u16,
-    flags: u16,
-    len: u16,
-    buffer: *mut u8,
+    pub(crate) addr: u16,
+    pub(crate) flags: u16,
+    pub(crate) len: u16,
+    pub(crate) buffer: *mut u8,
+}
+
+// i2c_smbus_data union, as defined in i2c.h
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union I2cSmbusData {
+    byte: u8,
+    word: u16,
+    // one extra byte for the length and one for PEC
+    block: [u8; I2C_SMBUS_BLOCK_MAX + 2],
+}
+
+// every bit pattern is valid for each field, and the union is always fully
+// initialised through block, so reading any field is safe
+impl I2cSmbusData {
+    pub fn new() -> Self {
+        Self {
+            block: [0; I2C_SMBUS_BLOCK_MAX + 2],
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        let mut data = Self::new();
+        data.byte = byte;
+        data
+    }
+
+    pub fn from_word(word: u16) -> Self {
+        let mut data = Self::new();
+        data.word = word;
+        data
+    }
+
+    // the caller must ensure block is no longer than I2C_SMBUS_BLOCK_MAX
+    pub fn from_block(block: &[u8]) -> Self {
+        let len = block.len();
+        let mut buffer = [0; I2C_SMBUS_BLOCK_MAX + 2];
+        buffer[0] = len as u8;
+        buffer[1..=len].copy_from_slice(block);
+        Self { block: buffer }
+    }
+
+    pub fn byte(&self) -> u8 {
+        unsafe { self.byte }
+    }
+
+    pub fn word(&self) -> u16 {
+        unsafe { self.word }
+    }
+
+    // returns the block data with the leading length byte stripped
+    pub fn block(&self) -> &[u8] {
+        let block = unsafe { &self.block };
+        let len = usize::from(block[0]).min(I2C_SMBUS_BLOCK_MAX);
+        &block[1..=len]
+    }
+}
+
+// i2c_smbus_ioctl_data struct, as defined in i2c-dev.h
+#[repr(C)]
+#[derive(Debug)]
+pub struct I2cSmbusIoctlData<'a> {
+    read_write: u8,
+    command: u8,
+    size: u32,
+    data: *mut I2cSmbusData,
+    // ensure that I2cSmbusIoctlData does not outlive the data
+    // it points to
+    _phantom: std::marker::PhantomData<&'a mut I2cSmbusData>,
+}
+
+impl<'a> I2cSmbusIoctlData<'a> {
+    pub fn new(read_write: u8, command: u8, size: u32, data: &'a mut I2cSmbusData) -> Self {
+        Self {
+            read_write,
+            command,
+            size,
+            data,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    // whether data is read from the device, not counting the command byte
+    pub(crate) fn reads(&self) -> bool {
+        match self.size {
+            I2C_SMBUS_QUICK => false,
+            I2C_SMBUS_PROC_CALL | I2C_SMBUS_BLOCK_PROC_CALL => true,
+            _ => self.read_write == I2C_SMBUS_READ,
+        }
+    }
+
+    // whether data is written to the device. the command byte alone only
+    // selects what is read, except for a write byte, which sends it as data
+    pub(crate) fn writes(&self) -> bool {
+        self.size != I2C_SMBUS_QUICK && self.read_write == I2C_SMBUS_WRITE
+    }
+}
+
+impl I2cMessage {
+    pub fn addr(&self) -> u16 {
+        self.addr
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn len(&self) -> u16 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.flags & I2C_M_RD > 0
+    }
+}
+
+// e.g. "0x76 read 2 bytes [TEN|NOSTART]"
+impl std::fmt::Display for I2cMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = match self.flags & I2C_M_RD {
+            0 => "write",
+            _ => "read",
+        };
+        write!(f, "{:#04x} {} {} bytes", self.addr, direction, self.len)?;
+
+        let mut names = FLAG_NAMES
+            .iter()
+            .filter(|(flag, _)| self.flags & flag > 0)
+            .map(|(_, name)| *name);
+        if let Some(name) = names.next() {
+            write!(f, " [{}", name)?;
+            for name in names {
+                write!(f, "|{}", name)?;
+            }
+            write!(f, "]")?;
+        }
+
+        Ok(())
+    }
+}
+
+// one message per line, in the order they are transferred
+impl std::fmt::Display for I2cMessageBuffer<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, message) in self.buffer.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", message)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_ten_bit_flag() {
+    let mut buffer = [0; 2];
+    let (seven, ten) = buffer.split_at_mut(1);
+    let messages = I2cMessageBuffer::new()
+        .add_read(0x76, 0, seven)
+        .unwrap()
+        .add_write(0x176, 0, ten)
+        .unwrap();
+
+    assert_eq!(messages.buffer[0].flags, I2C_M_RD);
+    assert_eq!(messages.buffer[1].flags, I2C_M_TEN);
+}
+
+#[test]
+fn test_length_error() {
+    let buffer = vec![0; 0x10000];
+    let result = I2cMessageBuffer::new()
+        .add_write(0x76, 0, &buffer)
+        .unwrap_err();
+    assert_eq!(
+        format!("{}", result),
+        "buffer of 65536 bytes exceeds the maximum message length"
+    );
+}
+
+#[test]
+fn test_empty_read() {
+    let result = I2cMessageBuffer::new().add_read(0x76, 0, &mut []);
+    assert!(matches!(result, Err(I2cError::EmptyBuffer)));
+
+    let register = 0xD0;
+    let result = I2cMessageBuffer::new().add_read_reg(0x76, 0, &register, &mut []);
+    assert!(matches!(result, Err(I2cError::EmptyBuffer)));
+
+    let messages = I2cMessageBuffer::new().add_write(0x76, 0, &[]).unwrap();
+    assert_eq!(messages.len(), 1);
+}
+
+#[test]
+fn test_too_many_messages() {
+    let buffer = [0; 43];
+    let messages = buffer
+        .chunks(1)
+        .try_fold(I2cMessageBuffer::new(), |messages, chunk| {
+            messages.add_write(0x76, 0, chunk)
+        })
+        .unwrap();
+
+    let result = I2cReadWriteData::from_messages(&messages).unwrap_err();
+    assert_eq!(
+        format!("{}", result),
+        "43 messages exceeds the maximum of 42 in a single transfer"
+    );
+}
+
+#[test]
+fn test_recycle() {
+    let mut buffer = [0; 2];
+    let messages = I2cMessageBuffer::new()
+        .add_read(0x76, 0, &mut buffer)
+        .unwrap();
+
+    let recycled = messages.recycle();
+    assert!(recycled.is_empty());
+    assert!(recycled.buffer.capacity() >= 1);
+
+    // the original buffer is no longer borrowed
+    buffer[0] = 1;
+    let mut other = [0; 2];
+    let messages = recycled.add_read(0x76, 0, &mut other).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(buffer, [1, 0]);
+}
+
+#[test]
+fn test_from_slice() {
+    let mut buffer = [0; 2];
+    let messages = I2cMessageBuffer::new()
+        .add_read(0x76, 0, &mut buffer)
+        .unwrap();
+    let copied = messages.messages().to_vec();
+
+    // SAFETY: messages, which borrows buffer, outlives data
+    let data = unsafe { I2cReadWriteData::from_slice(&copied) }.unwrap();
+    assert_eq!(data.messages(), messages.messages());
+
+    let copied = vec![copied[0]; 43];
+    let result = unsafe { I2cReadWriteData::from_slice(&copied) };
+    assert!(matches!(
+        result,
+        Err(I2cError::TooManyMessages { count: 43 })
+    ));
+}
+
+#[test]
+fn test_buffer_alias() {
+    let mut buffer = [0; 4];
+    let pointer = buffer.as_mut_ptr();
+    // SAFETY: both messages point into buffer, which outlives them
+    let messages = unsafe {
+        I2cMessageBuffer::new()
+            .add_raw(0x76, 0, 2, pointer)
+            .add_raw(0x76, I2C_M_RD, 2, pointer.add(1))
+    };
+    assert!(matches!(
+        check_aliasing(&messages.buffer),
+        Err(I2cError::BufferAlias)
+    ));
+
+    // writes from the same memory are fine, as nothing is written to it
+    let messages = unsafe {
+        I2cMessageBuffer::new()
+            .add_raw(0x76, 0, 2, pointer)
+            .add_raw(0x76, 0, 2, pointer.add(1))
+            .add_raw(0x76, I2C_M_RD, 1, pointer.add(3))
+    };
+    assert!(check_aliasing(&messages.buffer).is_ok());
+}
+
+#[test]
+fn test_append() {
+    let register = 0xD0;
+    let mut buffer = [0; 2];
+    let mut messages = I2cMessageBuffer::new()
+        .add_write(0x76, 0, std::slice::from_ref(&register))
+        .unwrap();
+    let read = I2cMessageBuffer::new()
+        .add_read(0x76, 0, &mut buffer)
+        .unwrap();
+    messages.append(read).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert!(messages.messages()[1].is_read());
+
+    let full = (0..41).try_fold(I2cMessageBuffer::new(), |messages, _| {
+        messages.add_write(0x76, 0, &[])
+    });
+    let result = messages.append(full.unwrap());
+    assert!(matches!(
+        result,
+        Err(I2cError::TooManyMessages { count: 43 })
+    ));
+    assert_eq!(messages.len(), 2);
+}
+
+#[test]
+fn test_with_capacity() {
+    let messages = I2cMessageBuffer::with_capacity(4);
+    assert!(messages.is_empty());
+    assert!(messages.buffer.capacity() >= 4);
+}
+
+#[test]
+fn test_read_reg_flags() {
+    let register = 0xD0;
+    let mut buffer = [0; 2];
+    let messages = I2cMessageBuffer::new()
+        .add_read_reg(0x76, I2C_M_RD | I2C_M_NOSTART, &register, &mut buffer)
+        .unwrap();
+
+    let flags: Vec<_> = messages.messages().iter().map(I2cMessage::flags).collect();
+    assert_eq!(flags, [I2C_M_NOSTART, I2C_M_RD | I2C_M_NOSTART]);
+}
+
+#[test]
+fn test_flag_error() {
+    let result = I2cMessageBuffer::new().add_write(0x76, I2C_M_RD, &[0xD0]);
+    assert!(matches!(
+        result,
+        Err(I2cError::FlagError { flags: I2C_M_RD })
+    ));
+    let result = I2cMessageBuffer::new().add_write(0x76, I2C_M_RECV_LEN, &[0xD0]);
+    assert!(matches!(result, Err(I2cError::FlagError { .. })));
+    assert!(check_flags(I2C_M_NO_RD_ACK).is_err());
+    assert!(check_flags(I2C_M_RD | I2C_M_RECV_LEN).is_ok());
+}
+
+#[test]
+fn test_read_reg16() {
+    let register = 0x0123u16.to_be_bytes();
+    let mut buffer = [0; 4];
+    let messages = I2cMessageBuffer::new()
+        .add_read_reg16(0x50, 0, &register, &mut buffer)
+        .unwrap();
+
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages.buffer[0].len, 2);
+    assert_eq!(unsafe { *messages.buffer[0].buffer }, 0x01);
+    assert_eq!(messages.buffer[1].len, 4);
+}
+
+#[test]
+fn test_display() {
+    let register = [0xD0];
+    let mut buffer = [0; 2];
+    let messages = I2cMessageBuffer::new()
+        .add_write(0x76, 0, &register)
+        .unwrap()
+        .add_read(0x176, I2C_M_NOSTART, &mut buffer)
+        .unwrap();
+
+    assert_eq!(
+        format!("{}", messages),
+        "0x76 write 1 bytes\n0x176 read 2 bytes [TEN|NOSTART]"
+    );
+}
+
+#[test]
+fn test_message_flags() {
+    let flags = MessageFlags::new().nostart().ignore_nack();
+    assert_eq!(u16::from(flags), I2C_M_NOSTART | I2C_M_IGNORE_NACK);
+    assert_eq!(u16::from(MessageFlags::new()), 0);
+}
+
+#[test]
+fn test_inspect_messages() {
+    let register = [0xD0];
+    let mut buffer = [0; 2];
+    let messages = I2cMessageBuffer::new()
+        .add_read_reg(0x76, 0, &register[0], &mut buffer)
+        .unwrap();
+
+    let shape: Vec<_> = messages
+        .messages()
+        .iter()
+        .map(|message| (message.addr(), message.is_read(), message.len()))
+        .collect();
+    assert_eq!(shape, [(0x76, false, 1), (0x76, true, 2)]);
+    assert_eq!(messages.total_bytes(), 3);
+}
+
+#[test]
+fn test_as_raw_parts() {
+    let register = [0xD0];
+    let mut buffer = [0; 2];
+    let messages = I2cMessageBuffer::new()
+        .add_read_reg(0x76, 0, &register[0], &mut buffer)
+        .unwrap();
+
+    let (ptr, len) = messages.as_raw_parts();
+    assert_eq!(len, 2);
+    // SAFETY: ptr and len come from messages, which is still borrowed
+    let raw = unsafe { std::slice::from_raw_parts(ptr, len) };
+    assert_eq!(raw, messages.messages());
 }
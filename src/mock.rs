@@ -0,0 +1,476 @@
+use crate::messages::I2cReadWriteData;
+use crate::{Functionality, I2cTransport};
+use libc::c_ulong;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+
+// an in-memory device for testing without hardware. as on most devices, the
+// first byte written sets the register pointer, and every byte read or written
+// after it advances the pointer. registers that were never written read as 0.
+// it reports plain i2c functionality only unless told otherwise, and accepts
+// any setting passed by value, e.g. I2C_SLAVE, except binding an address
+// claimed by a kernel driver without I2C_SLAVE_FORCE
+#[derive(Debug)]
+pub struct MockTransport {
+    func: Functionality,
+    claimed: Vec<u16>,
+    hangs: bool,
+    state: Mutex<MockState>,
+}
+
+impl Default for MockTransport {
+    fn default() -> Self {
+        Self {
+            func: Functionality::I2C,
+            claimed: Vec::new(),
+            hangs: false,
+            state: Mutex::new(MockState::default()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    registers: HashMap<u8, u8>,
+    pointer: u8,
+    // flags of each message of the last I2C_RDWR transfer
+    flags: Vec<u16>,
+    busy: bool,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_registers<I: IntoIterator<Item = (u8, u8)>>(registers: I) -> Self {
+        let state = MockState {
+            registers: registers.into_iter().collect(),
+            ..MockState::default()
+        };
+        Self {
+            state: Mutex::new(state),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_functionality(mut self, func: Functionality) -> Self {
+        self.func = func;
+        self
+    }
+
+    // marks addr as in use by a kernel driver, so binding it fails with EBUSY
+    pub fn with_claimed(mut self, addr: u16) -> Self {
+        self.claimed.push(addr);
+        self
+    }
+
+    // the device stops acknowledging after the first write of data, as a
+    // memory that never finishes storing a page
+    pub fn with_hang_after_write(mut self) -> Self {
+        self.hangs = true;
+        self
+    }
+
+    pub fn register(&self, register: u8) -> u8 {
+        let state = self.state.lock().unwrap();
+        state.registers.get(&register).copied().unwrap_or(0)
+    }
+
+    pub fn last_flags(&self) -> Vec<u16> {
+        self.state.lock().unwrap().flags.clone()
+    }
+
+    pub fn set_register(&self, register: u8, value: u8) {
+        let mut state = self.state.lock().unwrap();
+        state.registers.insert(register, value);
+    }
+}
+
+impl crate::transport::sealed::Sealed for MockTransport {}
+
+impl I2cTransport for MockTransport {
+    fn functionality(&self) -> io::Result<Functionality> {
+        Ok(self.func)
+    }
+
+    fn set(&self, request: c_ulong, value: c_ulong) -> io::Result<()> {
+        let claimed = self
+            .claimed
+            .iter()
+            .any(|addr| c_ulong::from(*addr) == value);
+        match request {
+            crate::I2C_SLAVE if claimed => Err(io::Error::from_raw_os_error(libc::EBUSY)),
+            _ => Ok(()),
+        }
+    }
+
+    fn rdwr(&self, data: &I2cReadWriteData) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        state.flags = data
+            .messages()
+            .iter()
+            .map(|message| message.flags)
+            .collect();
+        if state.busy {
+            return Err(io::Error::from_raw_os_error(libc::ENXIO));
+        }
+        for message in data.messages() {
+            let len = message.len as usize;
+            if message.is_read() {
+                // SAFETY:
+                // read messages are only added from buffers borrowed mutably
+                // for as long as data, so nothing else can access them
+                let buffer = unsafe { std::slice::from_raw_parts_mut(message.buffer, len) };
+                for byte in buffer.iter_mut() {
+                    *byte = state.registers.get(&state.pointer).copied().unwrap_or(0);
+                    state.pointer = state.pointer.wrapping_add(1);
+                }
+                continue;
+            }
+
+            // SAFETY:
+            // the buffers the messages point to are borrowed by data, write
+            // messages possibly only shared, so they are only read here
+            let buffer = unsafe { std::slice::from_raw_parts(message.buffer, len) };
+            if let Some((register, values)) = buffer.split_first() {
+                state.busy = self.hangs && !values.is_empty();
+                state.pointer = *register;
+                for value in values {
+                    let pointer = state.pointer;
+                    state.registers.insert(pointer, *value);
+                    state.pointer = pointer.wrapping_add(1);
+                }
+            }
+        }
+        Ok(data.messages().len())
+    }
+}
+
+#[test]
+fn test_mock_read_write() {
+    let mock = MockTransport::with_registers([(0xD0, 0x61)]);
+    let handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+
+    assert_eq!(handle.i2c_read_bytes(0xD0, 1).unwrap(), [0x61]);
+    handle.i2c_write(0x72, &[1, 2]).unwrap();
+    handle.flush().unwrap();
+    assert_eq!(handle.i2c_read_bytes(0x72, 2).unwrap(), [1, 2]);
+    assert_eq!(handle.read_u16_be(0x72).unwrap(), 0x0102);
+    let (id, _) = handle.i2c_read_timed(0xD0, 1).unwrap();
+    assert_eq!(id, [0x61]);
+    let delay = std::time::Duration::from_millis(1);
+    let id = handle.read_with_conversion_delay(0xD0, 1, delay).unwrap();
+    assert_eq!(id, [0x61]);
+    handle.write_byte(0x74, 0x05).unwrap();
+    assert_eq!(handle.read_byte(0x74).unwrap(), 0x05);
+}
+
+#[test]
+fn test_write_read() {
+    let mock = MockTransport::with_registers([(0x10, 0xAA), (0x11, 0xBB)]);
+    let handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+
+    let mut buffer = [0; 2];
+    handle.write_read(&[0x10], &mut buffer).unwrap();
+    assert_eq!(buffer, [0xAA, 0xBB]);
+}
+
+#[test]
+fn test_read_uninit() {
+    let mock = MockTransport::with_registers([(0xD0, 0x61), (0xD1, 0x62)]);
+    let handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+
+    let mut buffer = [std::mem::MaybeUninit::uninit(); 2];
+    let read = handle.i2c_read_uninit(0xD0, &mut buffer).unwrap();
+    assert_eq!(read, [0x61, 0x62]);
+}
+
+#[test]
+fn test_buffer() {
+    let handle = crate::I2c::from_transport(MockTransport::new(), 0x76).unwrap();
+    let flags = crate::MessageFlags::new();
+    let mut buffer = [0; 1];
+    let messages = handle
+        .i2c_buffer()
+        .add_write_at(0x77, 0, &[0xD0])
+        .unwrap()
+        .add_read_with(flags, &mut buffer)
+        .unwrap();
+    messages.execute().unwrap();
+    assert_eq!(
+        messages.to_string(),
+        "0x77 write 1 bytes\n0x76 read 1 bytes"
+    );
+
+    let mut word = [0; 2];
+    let messages = handle
+        .i2c_buffer()
+        .add_write_with(flags, &[0x72, 0x01, 0x02])
+        .unwrap()
+        .add_write(0, &[0x72])
+        .unwrap()
+        .add_read(0, &mut word)
+        .unwrap();
+    messages.execute().unwrap();
+    assert_eq!(messages.read_u16_be(2), Some(0x0102));
+    assert_eq!(messages.read_u16_le(2), Some(0x0201));
+    assert_eq!(messages.read_u32_be(2), None);
+    assert_eq!(messages.read_u16_be(1), None);
+
+    let result = handle.i2c_buffer().add_write_at(0x177, 0, &[0xD0]);
+    assert!(matches!(result, Err(crate::I2cError::AddressRangeError)));
+}
+
+#[test]
+fn test_forced_claimed() {
+    let mock = MockTransport::with_registers([(0xD0, 0x61)]).with_claimed(0x76);
+    let handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+    // opening probes over I2C_RDWR, which does not bind the address
+    handle.check_responds().unwrap();
+    let result = handle.bind();
+    assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EBUSY));
+
+    let mock = MockTransport::with_registers([(0xD0, 0x61)]).with_claimed(0x76);
+    let mut handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+    handle.force = true;
+    handle.check_responds().unwrap();
+    handle.bind().unwrap();
+    assert_eq!(handle.i2c_read_bytes(0xD0, 1).unwrap(), [0x61]);
+}
+
+#[test]
+fn test_dump_registers() {
+    let mock = MockTransport::with_registers([(0x00, 0x01), (0xD0, 0x61), (0xFF, 0x02)]);
+    let handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+
+    let registers = handle.dump_registers().unwrap();
+    assert_eq!(registers[0x00], 0x01);
+    assert_eq!(registers[0xD0], 0x61);
+    assert_eq!(registers[0xFF], 0x02);
+    assert_eq!(registers.iter().filter(|value| **value != 0).count(), 3);
+}
+
+#[test]
+fn test_access_mode() {
+    use crate::{AccessMode, I2cError};
+
+    // SMBUS transfers are checked before the mock rejects them as unsupported
+    let func = Functionality::I2C
+        | Functionality::SMBUS_READ_BYTE_DATA
+        | Functionality::SMBUS_WRITE_BYTE_DATA;
+    let mock = MockTransport::with_registers([(0xD0, 0x61)]).with_functionality(func);
+    let mut handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+    handle.access = AccessMode::ReadOnly;
+    assert_eq!(handle.read_byte(0xD0).unwrap(), 0x61);
+    assert!(handle.probe().unwrap());
+    assert!(matches!(
+        handle.write_byte(0x72, 1),
+        Err(I2cError::AccessDenied)
+    ));
+    assert!(matches!(
+        handle.smbus_write_byte_data(0x72, 1),
+        Err(I2cError::AccessDenied)
+    ));
+
+    handle.access = AccessMode::WriteOnly;
+    handle.write_byte(0x72, 1).unwrap();
+    assert!(matches!(
+        handle.read_byte(0xD0),
+        Err(I2cError::AccessDenied)
+    ));
+    assert!(matches!(
+        handle.smbus_read_byte_data(0xD0),
+        Err(I2cError::AccessDenied)
+    ));
+    assert_eq!(handle.transport.register(0x72), 1);
+}
+
+#[test]
+fn test_alert_respond() {
+    // the mock answers any address, so reads the alert response from register 0
+    let mock = MockTransport::with_registers([(0x00, 0x76 << 1 | 1)]);
+    let handle = crate::I2c::from_transport(mock, 0x50).unwrap();
+    assert_eq!(handle.smbus_alert_respond().unwrap(), Some((0x76, 1)));
+}
+
+#[test]
+fn test_io_unbound() {
+    use std::io::Write;
+
+    let mut handle = crate::I2c::from_transport(MockTransport::new(), 0x76).unwrap();
+    let err = handle.write(&[0x72, 1]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+
+    // bound, the write reaches the mock, which has no plain writes
+    handle.bind().unwrap();
+    assert!(handle.is_bound());
+    let err = handle.write(&[0x72, 1]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn test_refresh_functionality() {
+    let mut handle = crate::I2c::from_transport(MockTransport::new(), 0x76).unwrap();
+    handle.transport.func = Functionality::I2C | Functionality::SMBUS_QUICK;
+    assert_eq!(*handle.functionality(), Functionality::I2C);
+    handle.refresh_functionality().unwrap();
+    assert!(handle.functionality().smbus_quick());
+}
+
+#[test]
+fn test_write_paged() {
+    let handle = crate::I2c::from_transport(MockTransport::new(), 0x50).unwrap();
+    let data = [1, 2, 3, 4, 5, 6];
+    assert_eq!(handle.write_paged(0x0E, &data, 4, 1).unwrap(), 6);
+    assert_eq!(handle.i2c_read_bytes(0x0E, 6).unwrap(), data);
+
+    let result = handle.write_paged(0xFE, &data, 4, 1);
+    assert!(matches!(result, Err(crate::I2cError::AddressRangeError)));
+
+    let mock = MockTransport::new().with_hang_after_write();
+    let handle = crate::I2c::from_transport(mock, 0x50).unwrap();
+    let result = handle.write_paged(0x0E, &data, 4, 1).unwrap_err();
+    assert_eq!(result.raw_os_error(), Some(libc::ETIMEDOUT));
+}
+
+#[test]
+fn test_read_recv_len() {
+    // the first register holds the length of the block that follows it
+    let mock = MockTransport::with_registers([(0x10, 2), (0x11, 0xAA), (0x12, 0xBB)]);
+    let handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+
+    let mut buffer = [0; 33];
+    assert_eq!(handle.i2c_read_recv_len(0x10, &mut buffer).unwrap(), 2);
+    assert_eq!(buffer[1..3], [0xAA, 0xBB]);
+    assert_eq!(handle.i2c_read(0x11, &mut buffer[..2]).unwrap(), 2);
+}
+
+#[test]
+fn test_update_register() {
+    let mock = MockTransport::with_registers([(0x74, 0b1010_0000)]);
+    let handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+
+    assert_eq!(handle.set_bits(0x74, 0b0000_0011).unwrap(), 0b1010_0011);
+    assert_eq!(handle.clear_bits(0x74, 0b1000_0001).unwrap(), 0b0010_0010);
+    let value = handle.update_register(0x74, |value| value << 1).unwrap();
+    assert_eq!(value, 0b0100_0100);
+    assert_eq!(handle.i2c_read_bytes(0x74, 1).unwrap(), [0b0100_0100]);
+}
+
+#[test]
+fn test_set_addr() {
+    let mut handle = crate::I2c::from_transport(MockTransport::new(), 0x76).unwrap();
+    handle.set_addr(0x77).unwrap();
+    assert_eq!(handle.addr(), 0x77);
+    assert!(!handle.is_ten_bit());
+
+    let result = handle.set_addr(0x177);
+    assert!(matches!(result, Err(crate::I2cError::AddressRangeError)));
+    assert_eq!(handle.addr(), 0x77);
+}
+
+#[test]
+fn test_set_ten_bit() {
+    let handle = crate::I2c::from_transport(MockTransport::new(), 0x76).unwrap();
+    let result = handle.set_ten_bit(true);
+    assert!(matches!(
+        result,
+        Err(crate::I2cError::IoctlError(
+            crate::IoctlError::FunctionalityError(Functionality::TEN_BIT_ADDR)
+        ))
+    ));
+
+    let mock =
+        MockTransport::new().with_functionality(Functionality::I2C | Functionality::TEN_BIT_ADDR);
+    let mut handle = crate::I2c::from_transport(mock, 0x76).unwrap();
+    handle.set_addr(0x177).unwrap();
+    assert!(handle.is_ten_bit());
+    let result = handle.set_ten_bit(false);
+    assert!(matches!(result, Err(crate::I2cError::AddressRangeError)));
+    handle.set_addr(0x77).unwrap();
+    handle.set_ten_bit(false).unwrap();
+    assert!(!handle.is_ten_bit());
+
+    // opening at a 10 bit address applies the same rule as set_addr
+    let mock =
+        MockTransport::new().with_functionality(Functionality::I2C | Functionality::TEN_BIT_ADDR);
+    let handle = crate::I2c::from_transport(mock, 0x177).unwrap();
+    assert!(handle.is_ten_bit());
+}
+
+#[test]
+fn test_ten_bit_messages() {
+    use crate::{I2C_M_RD, I2C_M_TEN};
+
+    let func = Functionality::I2C | Functionality::TEN_BIT_ADDR;
+    let mock = MockTransport::with_registers([(0xD0, 0x61)]).with_functionality(func);
+    let handle = crate::I2c::from_transport(mock, 0x50).unwrap();
+    handle.i2c_read_bytes(0xD0, 1).unwrap();
+    assert_eq!(handle.transport.last_flags(), [0, I2C_M_RD]);
+
+    // in 10 bit mode every message is flagged, even for a 7 bit address
+    handle.set_ten_bit(true).unwrap();
+    assert_eq!(handle.i2c_read_bytes(0xD0, 1).unwrap(), [0x61]);
+    assert_eq!(
+        handle.transport.last_flags(),
+        [I2C_M_TEN, I2C_M_TEN | I2C_M_RD]
+    );
+    handle.i2c_write(0x72, &[1]).unwrap();
+    assert_eq!(handle.transport.last_flags(), [I2C_M_TEN]);
+    let mut buffer = [0];
+    handle
+        .i2c_buffer()
+        .add_read(0, &mut buffer)
+        .unwrap()
+        .execute()
+        .unwrap();
+    assert_eq!(handle.transport.last_flags(), [I2C_M_TEN | I2C_M_RD]);
+}
+
+#[test]
+fn test_mock_unsupported() {
+    let handle = crate::I2c::from_transport(MockTransport::new(), 0x76).unwrap();
+    let result = handle.smbus_read_byte_data(0xD0);
+    assert!(matches!(
+        result,
+        Err(crate::I2cError::ReadError {
+            addr: 0x76,
+            register: Some(0xD0),
+            source: crate::IoctlError::FunctionalityError(_),
+        })
+    ));
+}
+
+#[test]
+fn test_partial_transfer() {
+    // answers only the first message of each transfer
+    struct Partial;
+
+    impl crate::transport::sealed::Sealed for Partial {}
+
+    impl I2cTransport for Partial {
+        fn functionality(&self) -> io::Result<Functionality> {
+            Ok(Functionality::I2C)
+        }
+
+        fn rdwr(&self, data: &I2cReadWriteData) -> io::Result<usize> {
+            Ok(data.messages().len().min(1))
+        }
+    }
+
+    let handle = crate::I2c::from_transport(Partial, 0x76).unwrap();
+    let result = handle.i2c_read_bytes(0xD0, 1).unwrap_err();
+    assert!(matches!(
+        result,
+        crate::I2cError::ReadError {
+            source: crate::IoctlError::PartialTransfer {
+                completed: 1,
+                total: 2
+            },
+            ..
+        }
+    ));
+    handle.i2c_write(0x72, &[1]).unwrap();
+}
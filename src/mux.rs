@@ -0,0 +1,86 @@
+use crate::{I2c, I2cError, I2cResult, I2cTransport};
+
+// one bit of the control byte per channel
+const MUX_CHANNELS: u8 = 8;
+
+// a TCA9548A-style multiplexer, opened at the address of the mux itself. the
+// channel is selected by writing a single control byte with one bit set per
+// channel, after which downstream devices are reachable through other handles
+// on the same bus
+#[derive(Debug)]
+pub struct Mux<T = std::fs::File> {
+    handle: I2c<T>,
+}
+
+impl<T: I2cTransport> Mux<T> {
+    pub fn new(handle: I2c<T>) -> Self {
+        Self { handle }
+    }
+
+    pub fn select(&self, channel: u8) -> I2cResult<()> {
+        if channel >= MUX_CHANNELS {
+            return Err(I2cError::InvalidChannel { channel });
+        }
+        self.write_control(1 << channel)
+    }
+
+    // disconnects every downstream channel
+    pub fn deselect(&self) -> I2cResult<()> {
+        self.write_control(0)
+    }
+
+    // selects channel until the guard is dropped, when every channel is
+    // deselected again
+    pub fn select_scoped(&self, channel: u8) -> I2cResult<MuxGuard<'_, T>> {
+        self.select(channel)?;
+        Ok(MuxGuard { mux: self })
+    }
+
+    pub fn into_inner(self) -> I2c<T> {
+        self.handle
+    }
+
+    fn write_control(&self, channels: u8) -> I2cResult<()> {
+        // the control byte is all that is written, so it goes in place of the
+        // register with no data after it
+        self.handle.i2c_write(channels, &[])
+    }
+}
+
+#[derive(Debug)]
+pub struct MuxGuard<'a, T: I2cTransport = std::fs::File> {
+    mux: &'a Mux<T>,
+}
+
+impl<T: I2cTransport> Drop for MuxGuard<'_, T> {
+    fn drop(&mut self) {
+        // errors cannot be returned from drop, and a failure here leaves the
+        // channel selected just as not deselecting at all would
+        let _ = self.mux.deselect();
+    }
+}
+
+#[test]
+fn test_select() {
+    // an empty write then a read returns the register at the control byte
+    fn selected(mux: &Mux<crate::MockTransport>) -> u8 {
+        let mut value = [0];
+        mux.handle.write_read(&[], &mut value).unwrap();
+        value[0]
+    }
+
+    let mock = crate::MockTransport::with_registers([(0x00, 0xAA), (0x04, 0xBB)]);
+    let mux = Mux::new(I2c::from_transport(mock, 0x70).unwrap());
+
+    {
+        let _guard = mux.select_scoped(2).unwrap();
+        assert_eq!(selected(&mux), 0xBB);
+    }
+    assert_eq!(selected(&mux), 0xAA);
+
+    let result = mux.select(8);
+    assert!(matches!(
+        result,
+        Err(I2cError::InvalidChannel { channel: 8 })
+    ));
+}
@@ -0,0 +1,121 @@
+use crate::{I2c, I2cError, I2cResult, I2cTransport};
+use std::time::{Duration, Instant};
+
+// delay between reads, so waiting on a slow conversion does not saturate the bus
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+// repeatedly reads a single status register, e.g. until a device signals that
+// a conversion is complete
+#[derive(Debug)]
+pub struct Poller<'a, T = std::fs::File> {
+    handle: &'a I2c<T>,
+    register: u8,
+    interval: Duration,
+}
+
+impl<'a, T: I2cTransport> Poller<'a, T> {
+    pub fn new(handle: &'a I2c<T>, register: u8) -> Self {
+        Self {
+            handle,
+            register,
+            interval: POLL_INTERVAL,
+        }
+    }
+
+    pub fn with_interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
+    }
+
+    // reads until the masked register equals value, returning the last byte
+    // read. the register is always read at least once, even with a zero timeout
+    pub fn poll_until(&self, mask: u8, value: u8, timeout: Duration) -> I2cResult<u8> {
+        let start = Instant::now();
+        let mut buffer = [0];
+        loop {
+            self.handle.i2c_read(self.register, &mut buffer)?;
+            if buffer[0] & mask == value {
+                return Ok(buffer[0]);
+            }
+            if start.elapsed() >= timeout {
+                return Err(I2cError::Timeout);
+            }
+            std::thread::sleep(self.interval);
+        }
+    }
+}
+
+// an endless stream of reads of the same registers, e.g. for logging samples
+// with readings(0x1F, 3).take(1000). nothing is read until next is called
+#[derive(Debug)]
+pub struct Readings<'a, T = std::fs::File> {
+    handle: &'a I2c<T>,
+    register: u8,
+    bytes: usize,
+    interval: Duration,
+    started: bool,
+}
+
+impl<'a, T: I2cTransport> Readings<'a, T> {
+    // waits interval before every read but the first
+    pub fn with_interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
+    }
+}
+
+impl<T: I2cTransport> Iterator for Readings<'_, T> {
+    type Item = I2cResult<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.started && !self.interval.is_zero() {
+            std::thread::sleep(self.interval);
+        }
+        self.started = true;
+        Some(self.handle.i2c_read_bytes(self.register, self.bytes))
+    }
+}
+
+impl<T: I2cTransport> I2c<T> {
+    pub fn poller(&self, register: u8) -> Poller<'_, T> {
+        Poller::new(self, register)
+    }
+
+    pub fn readings(&self, register: u8, bytes: usize) -> Readings<'_, T> {
+        Readings {
+            handle: self,
+            register,
+            bytes,
+            interval: Duration::ZERO,
+            started: false,
+        }
+    }
+}
+
+#[test]
+fn test_poll_until() {
+    let mock = crate::MockTransport::with_registers([(0x1D, 0b1010_0000)]);
+    let handle = I2c::from_transport(mock, 0x76).unwrap();
+    let poller = handle.poller(0x1D);
+
+    assert_eq!(
+        poller
+            .poll_until(0b1000_0000, 0b1000_0000, Duration::ZERO)
+            .unwrap(),
+        0b1010_0000
+    );
+    let result = poller.poll_until(0b1000_0000, 0, Duration::from_millis(5));
+    assert!(matches!(result, Err(I2cError::Timeout)));
+}
+
+#[test]
+fn test_readings() {
+    let mock = crate::MockTransport::with_registers([(0x1F, 0x01), (0x20, 0x02)]);
+    let handle = I2c::from_transport(mock, 0x76).unwrap();
+
+    let samples: Vec<_> = handle
+        .readings(0x1F, 2)
+        .with_interval(Duration::from_millis(1))
+        .take(3)
+        .collect::<I2cResult<_>>()
+        .unwrap();
+    assert_eq!(samples, [[0x01, 0x02]; 3]);
+}
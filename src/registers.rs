@@ -0,0 +1,160 @@
+use crate::{I2c, I2cResult, I2cTransport};
+use std::marker::PhantomData;
+
+// register widths that can be read and written whole. wider registers are
+// taken to be big-endian, as on most sensors
+pub trait RegisterValue: Sized {
+    fn read_from<T: I2cTransport>(handle: &I2c<T>, register: u8) -> I2cResult<Self>;
+    fn write_to<T: I2cTransport>(self, handle: &I2c<T>, register: u8) -> I2cResult<()>;
+}
+
+impl RegisterValue for u8 {
+    fn read_from<T: I2cTransport>(handle: &I2c<T>, register: u8) -> I2cResult<Self> {
+        handle.read_byte(register)
+    }
+
+    fn write_to<T: I2cTransport>(self, handle: &I2c<T>, register: u8) -> I2cResult<()> {
+        handle.write_byte(register, self)
+    }
+}
+
+impl RegisterValue for u16 {
+    fn read_from<T: I2cTransport>(handle: &I2c<T>, register: u8) -> I2cResult<Self> {
+        handle.read_u16_be(register)
+    }
+
+    fn write_to<T: I2cTransport>(self, handle: &I2c<T>, register: u8) -> I2cResult<()> {
+        handle.write_u16_be(register, self)
+    }
+}
+
+impl RegisterValue for u32 {
+    fn read_from<T: I2cTransport>(handle: &I2c<T>, register: u8) -> I2cResult<Self> {
+        handle.read_u32_be(register)
+    }
+
+    fn write_to<T: I2cTransport>(self, handle: &I2c<T>, register: u8) -> I2cResult<()> {
+        handle.write_u32_be(register, self)
+    }
+}
+
+// a single register of a device, as returned by the accessors generated by
+// define_registers. read only and write only registers only have the one method
+#[derive(Debug)]
+pub struct Register<'a, V, T = std::fs::File> {
+    handle: &'a I2c<T>,
+    register: u8,
+    _value: PhantomData<V>,
+}
+
+#[derive(Debug)]
+pub struct ReadRegister<'a, V, T = std::fs::File>(Register<'a, V, T>);
+
+#[derive(Debug)]
+pub struct WriteRegister<'a, V, T = std::fs::File>(Register<'a, V, T>);
+
+impl<'a, V: RegisterValue, T: I2cTransport> Register<'a, V, T> {
+    pub fn new(handle: &'a I2c<T>, register: u8) -> Self {
+        Self {
+            handle,
+            register,
+            _value: PhantomData,
+        }
+    }
+
+    pub fn read(&self) -> I2cResult<V> {
+        V::read_from(self.handle, self.register)
+    }
+
+    pub fn write(&self, value: V) -> I2cResult<()> {
+        value.write_to(self.handle, self.register)
+    }
+}
+
+impl<'a, V: RegisterValue, T: I2cTransport> ReadRegister<'a, V, T> {
+    pub fn new(handle: &'a I2c<T>, register: u8) -> Self {
+        Self(Register::new(handle, register))
+    }
+
+    pub fn read(&self) -> I2cResult<V> {
+        self.0.read()
+    }
+}
+
+impl<'a, V: RegisterValue, T: I2cTransport> WriteRegister<'a, V, T> {
+    pub fn new(handle: &'a I2c<T>, register: u8) -> Self {
+        Self(Register::new(handle, register))
+    }
+
+    pub fn write(&self, value: V) -> I2cResult<()> {
+        self.0.write(value)
+    }
+}
+
+// generates a newtype over I2c with an accessor per register, e.g.
+//
+// define_registers! {
+//     pub Bme680 {
+//         chip_id: 0xD0 => u8 (ro),
+//         ctrl_meas: 0x74 => u8 (rw),
+//         reset: 0xE0 => u8 (wo),
+//     }
+// }
+//
+// let bme680 = Bme680(I2c::open(0x76)?);
+// bme680.ctrl_meas().write(bme680.ctrl_meas().read()? | 1)?;
+//
+// registers can be u8, or u16 and u32 read and written big-endian
+#[macro_export]
+macro_rules! define_registers {
+    (
+        $(#[$meta:meta])*
+        $vis:vis $name:ident {
+            $($register:ident: $addr:expr => $value:ident ($access:ident)),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<T = std::fs::File>(pub $crate::I2c<T>);
+
+        impl<T: $crate::I2cTransport> $name<T> {
+            $($crate::define_registers!(@accessor $register, $addr, $value, $access);)*
+        }
+    };
+    (@accessor $register:ident, $addr:expr, $value:ty, ro) => {
+        pub fn $register(&self) -> $crate::ReadRegister<'_, $value, T> {
+            $crate::ReadRegister::new(&self.0, $addr)
+        }
+    };
+    (@accessor $register:ident, $addr:expr, $value:ty, wo) => {
+        pub fn $register(&self) -> $crate::WriteRegister<'_, $value, T> {
+            $crate::WriteRegister::new(&self.0, $addr)
+        }
+    };
+    (@accessor $register:ident, $addr:expr, $value:ty, rw) => {
+        pub fn $register(&self) -> $crate::Register<'_, $value, T> {
+            $crate::Register::new(&self.0, $addr)
+        }
+    };
+}
+
+#[test]
+fn test_define_registers() {
+    crate::define_registers! {
+        Bme680 {
+            chip_id: 0xD0 => u8 (ro),
+            ctrl_meas: 0x74 => u8 (rw),
+            calibration: 0x8A => u16 (rw),
+            reset: 0xE0 => u8 (wo),
+        }
+    }
+
+    let mock = crate::MockTransport::with_registers([(0xD0, 0x61)]);
+    let bme680 = Bme680(I2c::from_transport(mock, 0x76).unwrap());
+
+    assert_eq!(bme680.chip_id().read().unwrap(), 0x61);
+    bme680.ctrl_meas().write(0b0010_0101).unwrap();
+    assert_eq!(bme680.ctrl_meas().read().unwrap(), 0b0010_0101);
+    bme680.calibration().write(0x0102).unwrap();
+    assert_eq!(bme680.0.i2c_read_bytes(0x8A, 2).unwrap(), [0x01, 0x02]);
+    bme680.reset().write(0xB6).unwrap();
+}
@@ -0,0 +1,223 @@
+use crate::messages::{I2cMessageBuffer, I2cReadWriteData};
+use crate::{i2c_rdwr_ioctl, I2c, I2cError, I2cResult, I2cTransport, IoctlError};
+
+// register widths that can be read with a single SMBUS transfer, u8 as byte
+// data and u16 as word data
+pub trait FromSmbus: Sized {
+    fn read_from<T: I2cTransport>(handle: &I2c<T>, command: u8) -> I2cResult<Self>;
+}
+
+pub trait IntoSmbus {
+    fn write_to<T: I2cTransport>(self, handle: &I2c<T>, command: u8) -> I2cResult<()>;
+}
+
+impl FromSmbus for u8 {
+    fn read_from<T: I2cTransport>(handle: &I2c<T>, command: u8) -> I2cResult<Self> {
+        handle.smbus_read_byte_data(command)
+    }
+}
+
+impl FromSmbus for u16 {
+    fn read_from<T: I2cTransport>(handle: &I2c<T>, command: u8) -> I2cResult<Self> {
+        handle.smbus_read_word_data(command)
+    }
+}
+
+impl IntoSmbus for u8 {
+    fn write_to<T: I2cTransport>(self, handle: &I2c<T>, command: u8) -> I2cResult<()> {
+        handle.smbus_write_byte_data(command, self)
+    }
+}
+
+impl IntoSmbus for u16 {
+    fn write_to<T: I2cTransport>(self, handle: &I2c<T>, command: u8) -> I2cResult<()> {
+        handle.smbus_write_word_data(command, self)
+    }
+}
+
+// byte and word data transfers built from plain I2C_RDWR messages, for
+// adapters that can do i2c but not the SMBUS transfer itself. the native
+// transfer is still used where it is supported. as in the kernel's own
+// emulation, with PEC enabled the PEC byte is appended to writes and read and
+// checked after reads, a mismatch failing with EBADMSG. PEC is not supported
+// for 10 bit addresses, whose header does not fit the single address byte
+impl<T: I2cTransport> I2c<T> {
+    pub fn smbus_read_byte_data_emulated(&self, command: u8) -> I2cResult<u8> {
+        if self.func.smbus_read_byte_data() {
+            return self.smbus_read_byte_data(command);
+        }
+        let mut value = [0];
+        self.emulated_read(command, &mut value)?;
+        Ok(value[0])
+    }
+
+    pub fn smbus_write_byte_data_emulated(&self, command: u8, value: u8) -> I2cResult<()> {
+        if self.func.smbus_write_byte_data() {
+            return self.smbus_write_byte_data(command, value);
+        }
+        self.emulated_write(command, &[value])
+    }
+
+    pub fn smbus_read_word_data_emulated(&self, command: u8) -> I2cResult<u16> {
+        if self.func.smbus_read_word_data() {
+            return self.smbus_read_word_data(command);
+        }
+        let mut value = [0; 2];
+        self.emulated_read(command, &mut value)?;
+        Ok(u16::from_le_bytes(value))
+    }
+
+    pub fn smbus_write_word_data_emulated(&self, command: u8, value: u16) -> I2cResult<()> {
+        if self.func.smbus_write_word_data() {
+            return self.smbus_write_word_data(command, value);
+        }
+        self.emulated_write(command, &value.to_le_bytes())
+    }
+
+    // value is at most a word, so the PEC byte always fits after it
+    fn emulated_read(&self, command: u8, value: &mut [u8]) -> I2cResult<()> {
+        let pec = self.emulated_pec()?;
+        let len = value.len();
+        let mut buffer = [0; 3];
+        {
+            let read = &mut buffer[..len + usize::from(pec)];
+            let messages = I2cMessageBuffer::new().add_read_reg(
+                self.addr,
+                self.addr_flags(),
+                &command,
+                read,
+            )?;
+            let data = I2cReadWriteData::from_messages(&messages)?;
+            i2c_rdwr_ioctl(self, &data).map_err(self.read_error(Some(command)))?;
+        }
+
+        if pec {
+            let addr = (self.addr << 1) as u8;
+            let crc = crc8(crc8(0, &[addr, command, addr | 1]), &buffer[..len]);
+            if crc != buffer[len] {
+                let err = std::io::Error::from_raw_os_error(libc::EBADMSG);
+                return Err(self.read_error(Some(command))(IoctlError::from(err)));
+            }
+        }
+        value.copy_from_slice(&buffer[..len]);
+        Ok(())
+    }
+
+    fn emulated_write(&self, command: u8, value: &[u8]) -> I2cResult<()> {
+        let mut buffer = [0; 4];
+        buffer[0] = command;
+        buffer[1..=value.len()].copy_from_slice(value);
+        let mut len = value.len() + 1;
+        if self.emulated_pec()? {
+            buffer[len] = crc8(crc8(0, &[(self.addr << 1) as u8]), &buffer[..len]);
+            len += 1;
+        }
+
+        let messages =
+            I2cMessageBuffer::new().add_write(self.addr, self.addr_flags(), &buffer[..len])?;
+        let data = I2cReadWriteData::from_messages(&messages)?;
+        i2c_rdwr_ioctl(self, &data).map_err(self.write_error(Some(command)))
+    }
+
+    fn emulated_pec(&self) -> I2cResult<bool> {
+        match self.pec_enabled() {
+            true if self.is_ten_bit() => Err(I2cError::AddressRangeError),
+            enabled => Ok(enabled),
+        }
+    }
+}
+
+// the SMBUS packet error code, a CRC-8 with polynomial x^8 + x^2 + x + 1 over
+// every byte of the transfer, address bytes included
+fn crc8(crc: u8, data: &[u8]) -> u8 {
+    data.iter().fold(crc, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
+
+#[test]
+fn test_width_dispatch() {
+    use crate::{Functionality, MockTransport};
+
+    // the mock has no SMBUS support, so the missing functionality shows which
+    // transfer each width uses
+    fn missing<V>(result: I2cResult<V>) -> Functionality {
+        match result {
+            Err(I2cError::ReadError {
+                source: IoctlError::FunctionalityError(missing),
+                ..
+            })
+            | Err(I2cError::WriteError {
+                source: IoctlError::FunctionalityError(missing),
+                ..
+            }) => missing,
+            _ => panic!("expected missing functionality"),
+        }
+    }
+
+    let handle = I2c::from_transport(MockTransport::new(), 0x76).unwrap();
+
+    assert_eq!(
+        missing(handle.smbus_read::<u8>(0xD0)),
+        Functionality::SMBUS_READ_BYTE_DATA
+    );
+    assert_eq!(
+        missing(handle.smbus_read::<u16>(0xD0)),
+        Functionality::SMBUS_READ_WORD_DATA
+    );
+    assert_eq!(
+        missing(handle.smbus_write(0x72, 1u8)),
+        Functionality::SMBUS_WRITE_BYTE_DATA
+    );
+    assert_eq!(
+        missing(handle.smbus_write(0x72, 1u16)),
+        Functionality::SMBUS_WRITE_WORD_DATA
+    );
+}
+
+#[test]
+fn test_crc8() {
+    assert_eq!(crc8(0, b"123456789"), 0xF4);
+}
+
+#[test]
+fn test_emulated() {
+    use crate::{Functionality, MockTransport};
+
+    let mock = MockTransport::with_registers([(0xD0, 0x61)]);
+    let handle = I2c::from_transport(mock, 0x76).unwrap();
+    assert_eq!(handle.smbus_read_byte_data_emulated(0xD0).unwrap(), 0x61);
+    handle.smbus_write_word_data_emulated(0x72, 0x0201).unwrap();
+    assert_eq!(handle.i2c_read_bytes(0x72, 2).unwrap(), [0x01, 0x02]);
+    assert_eq!(handle.smbus_read_word_data_emulated(0x72).unwrap(), 0x0201);
+
+    // the mock reads the register after the value back as the PEC byte
+    let mock = MockTransport::with_registers([(0xD0, 0x61)])
+        .with_functionality(Functionality::I2C | Functionality::SMBUS_PEC);
+    let handle = I2c::from_transport(mock, 0x76).unwrap();
+    assert!(!handle.pec_enabled());
+    handle.set_pec(true).unwrap();
+    assert!(handle.pec_enabled());
+    let result = handle.smbus_read_byte_data_emulated(0xD0);
+    assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EBADMSG));
+
+    let pec = crc8(0, &[0x76 << 1, 0xD0, 0x76 << 1 | 1, 0x61]);
+    handle.transport.set_register(0xD1, pec);
+    assert_eq!(handle.smbus_read_byte_data_emulated(0xD0).unwrap(), 0x61);
+
+    handle.smbus_write_byte_data_emulated(0x72, 0x05).unwrap();
+    let pec = crc8(0, &[0x76 << 1, 0x72, 0x05]);
+    assert_eq!(handle.i2c_read_bytes(0x72, 2).unwrap(), [0x05, pec]);
+
+    let func = Functionality::I2C | Functionality::SMBUS_PEC | Functionality::TEN_BIT_ADDR;
+    let mock = MockTransport::new().with_functionality(func);
+    let handle = I2c::from_transport(mock, 0x150).unwrap();
+    handle.set_pec(true).unwrap();
+    let result = handle.smbus_read_byte_data_emulated(0xD0);
+    assert!(matches!(result, Err(I2cError::AddressRangeError)));
+    let result = handle.smbus_write_byte_data_emulated(0x72, 0x05);
+    assert!(matches!(result, Err(I2cError::AddressRangeError)));
+}
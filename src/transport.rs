@@ -0,0 +1,84 @@
+use crate::messages::{I2cReadWriteData, I2cSmbusIoctlData};
+use crate::{get_err, Functionality, I2C_FUNCS, I2C_RDWR, I2C_SMBUS};
+use libc::{c_ulong, ioctl};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+
+// the layer that issues requests to the adapter, implemented by File for the
+// i2c-dev character devices. only the functionality query and I2C_RDWR
+// transfers are required, anything else is unsupported unless implemented.
+// sealed, as the messages and SMBUS data passed in keep their buffers private,
+// so File and MockTransport are the only supported implementations
+pub trait I2cTransport: sealed::Sealed {
+    fn functionality(&self) -> io::Result<Functionality>;
+
+    // returns the number of messages transferred
+    fn rdwr(&self, data: &I2cReadWriteData) -> io::Result<usize>;
+
+    fn smbus(&self, _data: &I2cSmbusIoctlData) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    // ioctl commands that take their argument by value, e.g. I2C_SLAVE
+    fn set(&self, _request: c_ulong, _value: c_ulong) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn read(&self, _buffer: &mut [u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn write(&self, _buffer: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait Sealed {}
+}
+
+impl sealed::Sealed for std::fs::File {}
+
+impl I2cTransport for std::fs::File {
+    fn functionality(&self) -> io::Result<Functionality> {
+        let mut func = 0;
+        get_err(unsafe { ioctl(self.as_raw_fd(), I2C_FUNCS, &mut func) })?;
+
+        Ok(Functionality(func))
+    }
+
+    fn rdwr(&self, data: &I2cReadWriteData) -> io::Result<usize> {
+        // SAFETY:
+        // file descriptor guaranteed to point to valid open file
+        // data guaranteed to outlast function call
+        // parameters correctly passed as described in i2c.h and i2c-dev.h
+        // hope ioctl implementation doesn't mess things up
+        let completed = get_err(unsafe { ioctl(self.as_raw_fd(), I2C_RDWR, data) })?;
+        Ok(completed as usize)
+    }
+
+    fn smbus(&self, data: &I2cSmbusIoctlData) -> io::Result<()> {
+        // SAFETY:
+        // file descriptor guaranteed to point to valid open file
+        // data and the union it points to guaranteed to outlast function call
+        // parameters correctly passed as described in i2c.h and i2c-dev.h
+        get_err(unsafe { ioctl(self.as_raw_fd(), I2C_SMBUS, data) })?;
+        Ok(())
+    }
+
+    fn set(&self, request: c_ulong, value: c_ulong) -> io::Result<()> {
+        // SAFETY:
+        // file descriptor guaranteed to point to valid open file
+        // value is passed directly rather than through a pointer
+        get_err(unsafe { ioctl(self.as_raw_fd(), request, value) })?;
+        Ok(())
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        Read::read(&mut &*self, buffer)
+    }
+
+    fn write(&self, buffer: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &*self, buffer)
+    }
+}